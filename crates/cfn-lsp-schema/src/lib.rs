@@ -4,6 +4,13 @@ use proc_macro2::{Ident, Punct, Spacing, Span, TokenStream};
 use quote::TokenStreamExt;
 use serde::Deserialize;
 
+pub use schema::{
+    HandlerSchema, PathSegment, PrimitiveType, PropertyAccess, PropertySchema, ResourceSchema,
+    resource_schema,
+};
+
+mod schema;
+
 #[derive(thiserror::Error, Debug)]
 pub enum SchemaError {
     #[error("extracting resource info from file {filename}")]
@@ -20,6 +27,8 @@ pub enum SchemaError {
     },
     #[error("zip archive error")]
     ZipError(#[from] zip::result::ZipError),
+    #[error("no schema for resource type {0}")]
+    UnknownResourceType(String),
 }
 
 pub type Result<T> = std::result::Result<T, SchemaError>;
@@ -169,68 +178,35 @@ pub struct ResourceInfo {
     pub write_only_properties: Vec<String>,
 }
 
+/// Name used with `Fn::GetAtt` for a read only property pointer such as
+/// `/properties/Endpoint/Address`
+///
+/// Properties of array items (`/properties/Tags/*/Key`) cannot be retrieved, so give `None`.
+pub(crate) fn attribute_name(read_only_property: &str) -> Option<String> {
+    let path = read_only_property
+        .strip_prefix("/properties/")
+        .unwrap_or(read_only_property);
+    if path.is_empty() || path.contains('*') {
+        return None;
+    }
+    Some(path.replace('/', "."))
+}
+
 #[derive(Deserialize)]
 struct Schema {
     #[serde(rename = "typeName")]
     type_name: String,
     description: Option<String>,
-    handlers: Option<HashMap<String, HashMap<String, serde_json::Value>>>,
-    #[serde(rename = "createOnlyProperties")]
-    create_only_properties: Option<Vec<String>>,
-    #[serde(rename = "primaryIdentifier")]
-    primary_identifier: Option<Vec<String>>,
-    #[serde(rename = "readOnlyProperties")]
-    read_only_properties: Option<Vec<String>>,
-    #[serde(rename = "writeOnlyProperties")]
-    write_only_properties: Option<Vec<String>>,
 }
 
-fn strip_properties_prefix(s: String) -> String {
-    s.replace("/properties/", "")
+fn strip_properties_prefix(pointers: &[String]) -> impl Iterator<Item = String> + '_ {
+    pointers.iter().map(|p| p.replace("/properties/", ""))
 }
 
-fn extract_from_file<R>(filename: &str, reader: R) -> Result<ResourceInfo>
-where
-    R: std::io::Read,
-{
-    let schema: Schema =
-        serde_json::from_reader(reader).map_err(|json_error| SchemaError::ParseJson {
-            filename: filename.to_string(),
-            json_error,
-        })?;
-
-    let mut resource_info = ResourceInfo {
-        type_name: schema.type_name,
-        description: schema.description,
-        handler_permissions: HashMap::new(),
-        read_only_properties: schema
-            .read_only_properties
-            .unwrap_or_default()
-            .into_iter()
-            .map(strip_properties_prefix)
-            .collect(),
-        write_only_properties: schema
-            .write_only_properties
-            .unwrap_or_default()
-            .into_iter()
-            .map(strip_properties_prefix)
-            .collect(),
-        create_only_properties: schema
-            .create_only_properties
-            .unwrap_or_default()
-            .into_iter()
-            .map(strip_properties_prefix)
-            .collect(),
-        primary_identifier: schema
-            .primary_identifier
-            .unwrap_or_default()
-            .into_iter()
-            .map(strip_properties_prefix)
-            .collect::<Vec<_>>()
-            .join("|"),
-    };
-    if let Some(handlers) = schema.handlers {
-        for (handler, details) in handlers {
+impl From<&ResourceSchema> for ResourceInfo {
+    fn from(schema: &ResourceSchema) -> Self {
+        let mut handler_permissions = HashMap::new();
+        for (handler, details) in &schema.handlers {
             let handler_type = match handler.as_str() {
                 "create" => Handler::Create,
                 "read" => Handler::Read,
@@ -238,19 +214,36 @@ where
                 "delete" => Handler::Delete,
                 _ => continue, // Ignore unknown handlers
             };
-            let permissions = details.get("permissions").cloned();
-            if let Some(serde_json::Value::Array(permissions)) = permissions {
-                let permissions = permissions
-                    .into_iter()
-                    .filter_map(|p| p.as_str().map(String::from))
-                    .collect();
-                resource_info
-                    .handler_permissions
-                    .insert(handler_type, Some(permissions));
+            if let Some(permissions) = &details.permissions {
+                handler_permissions.insert(handler_type, Some(permissions.clone()));
             }
         }
+
+        ResourceInfo {
+            type_name: schema.type_name.clone(),
+            description: schema.description.clone(),
+            handler_permissions,
+            read_only_properties: strip_properties_prefix(&schema.read_only_properties).collect(),
+            write_only_properties: strip_properties_prefix(&schema.write_only_properties).collect(),
+            create_only_properties: strip_properties_prefix(&schema.create_only_properties)
+                .collect(),
+            primary_identifier: strip_properties_prefix(&schema.primary_identifier)
+                .collect::<Vec<_>>()
+                .join("|"),
+        }
     }
-    Ok(resource_info)
+}
+
+fn extract_from_file<R>(filename: &str, reader: R) -> Result<ResourceInfo>
+where
+    R: std::io::Read,
+{
+    let schema: ResourceSchema =
+        serde_json::from_reader(reader).map_err(|json_error| SchemaError::ParseJson {
+            filename: filename.to_string(),
+            json_error,
+        })?;
+    Ok(ResourceInfo::from(&schema))
 }
 
 #[cfg(not(feature = "bundle"))]
@@ -273,20 +266,10 @@ fn schema_archive() -> Result<zip::ZipArchive<std::io::Cursor<&'static [u8]>>> {
     Ok(z)
 }
 
+/// Summary information for a resource type, loaded through the [`resource_schema`] cache
 pub fn extract_resource_from_bundle(resource_type: &str) -> Result<ResourceInfo> {
-    let mut z = schema_archive()?;
-    let name = format!(
-        "{}.json",
-        resource_type.to_ascii_lowercase().replace("::", "-")
-    );
-    tracing::debug!(%name, %resource_type, transformed_name = resource_type.to_ascii_lowercase().replace("::", "-"), "looking for resource in zip archive");
-    let zf = z.by_name(&name).map_err(SchemaError::ZipError)?;
-    let resource_info =
-        extract_from_file(&name, zf).map_err(|source| SchemaError::ExtractingResourceInfo {
-            filename: name,
-            source: Box::new(source),
-        })?;
-    Ok(resource_info)
+    let schema = resource_schema(resource_type)?;
+    Ok(ResourceInfo::from(schema.as_ref()))
 }
 
 fn extract_from_bundle<R>(reader: R) -> Result<Vec<ResourceInfo>>
@@ -379,4 +362,10 @@ mod tests {
         assert!(result.iter().any(|r| r.type_name == "AWS::IAM::Role"));
         assert!(result.iter().any(|r| r.type_name == "AWS::S3::Bucket"));
     }
+
+    #[test]
+    fn attributes_from_read_only_properties() {
+        assert_eq!(attribute_name("/properties/Arn").as_deref(), Some("Arn"));
+        assert_eq!(attribute_name("/properties/Tags/*/Key"), None);
+    }
 }
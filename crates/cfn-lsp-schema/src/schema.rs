@@ -0,0 +1,622 @@
+//! Full resource provider schemas
+//!
+//! Property schemas are kept as they appear in the provider schema, with `$ref` pointers
+//! unresolved. The [`ResourceSchema`] methods follow the pointers when navigating, so recursive
+//! definitions do not need to be expanded up front.
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{Result, SchemaError, schema_archive};
+
+/// Maximum number of `$ref` pointers followed when resolving a single schema
+const MAX_REFERENCE_DEPTH: usize = 32;
+
+/// JSON schema primitive type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrimitiveType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Object,
+    Array,
+    Null,
+}
+
+impl std::fmt::Display for PrimitiveType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PrimitiveType::String => "string",
+            PrimitiveType::Integer => "integer",
+            PrimitiveType::Number => "number",
+            PrimitiveType::Boolean => "boolean",
+            PrimitiveType::Object => "object",
+            PrimitiveType::Array => "array",
+            PrimitiveType::Null => "null",
+        })
+    }
+}
+
+/// Schema for a single property value
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertySchema {
+    pub description: Option<String>,
+    /// Allowed types; empty if the schema does not restrict the type
+    #[serde(rename = "type", default, deserialize_with = "one_or_many")]
+    pub types: Vec<PrimitiveType>,
+    /// Pointer to another schema in the same document, e.g. `#/definitions/Tag`
+    #[serde(rename = "$ref")]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub properties: BTreeMap<String, PropertySchema>,
+    #[serde(default)]
+    pub pattern_properties: BTreeMap<String, PropertySchema>,
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// Whether keys other than those in `properties` are allowed; unrestricted if absent
+    pub additional_properties: Option<bool>,
+    pub items: Option<Box<PropertySchema>>,
+    #[serde(rename = "enum")]
+    pub enum_values: Option<Vec<serde_json::Value>>,
+    #[serde(rename = "const")]
+    pub const_value: Option<serde_json::Value>,
+    pub pattern: Option<String>,
+    pub min_length: Option<u64>,
+    pub max_length: Option<u64>,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub min_items: Option<u64>,
+    pub max_items: Option<u64>,
+    #[serde(default)]
+    pub one_of: Vec<PropertySchema>,
+    #[serde(default)]
+    pub any_of: Vec<PropertySchema>,
+    #[serde(default)]
+    pub all_of: Vec<PropertySchema>,
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<PrimitiveType>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PrimitiveType),
+        Many(Vec<PrimitiveType>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(t) => vec![t],
+        OneOrMany::Many(ts) => ts,
+    })
+}
+
+/// Resource provider schema with its property definitions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceSchema {
+    pub type_name: String,
+    pub description: Option<String>,
+    /// The top level `properties`, `required` and `additionalProperties` of the resource
+    #[serde(flatten)]
+    pub root: PropertySchema,
+    #[serde(default)]
+    pub definitions: BTreeMap<String, PropertySchema>,
+    #[serde(default)]
+    pub read_only_properties: Vec<String>,
+    #[serde(default)]
+    pub create_only_properties: Vec<String>,
+    #[serde(default)]
+    pub write_only_properties: Vec<String>,
+    #[serde(default)]
+    pub deprecated_properties: Vec<String>,
+    /// Pointers to the properties that identify the resource, e.g. `/properties/BucketName`
+    #[serde(default)]
+    pub primary_identifier: Vec<String>,
+    /// The provider handlers, keyed by operation, e.g. `create`
+    #[serde(default)]
+    pub handlers: BTreeMap<String, HandlerSchema>,
+    /// The `pattern` and `patternProperties` patterns of every schema in the document, compiled
+    /// once when it is loaded; `None` if the pattern is not supported by the regex crate
+    #[serde(skip)]
+    patterns: HashMap<String, Option<Regex>>,
+}
+
+/// A handler of the resource provider
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HandlerSchema {
+    /// IAM permissions the handler needs
+    pub permissions: Option<Vec<String>>,
+}
+
+/// One step of a path into a resource's properties
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment<'a> {
+    /// Property of an object
+    Key(&'a str),
+    /// Item of an array
+    Item,
+}
+
+/// Restrictions on how a property can be set, from the `createOnlyProperties`,
+/// `writeOnlyProperties`, `readOnlyProperties` and `deprecatedProperties` of the schema
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PropertyAccess {
+    /// Changing the property replaces the resource
+    pub create_only: bool,
+    /// The property is never returned when the resource is read, e.g. a password
+    pub write_only: bool,
+    /// The property is set by the service and cannot be specified in a template
+    pub read_only: bool,
+    /// The property is still accepted but should no longer be used
+    pub deprecated: bool,
+}
+
+impl ResourceSchema {
+    fn from_reader<R>(filename: &str, reader: R) -> Result<Self>
+    where
+        R: std::io::Read,
+    {
+        let mut schema: Self =
+            serde_json::from_reader(reader).map_err(|json_error| SchemaError::ParseJson {
+                filename: filename.to_string(),
+                json_error,
+            })?;
+        let mut patterns = HashMap::new();
+        for property in std::iter::once(&schema.root).chain(schema.definitions.values()) {
+            compile_patterns(property, &mut patterns);
+        }
+        schema.patterns = patterns;
+        Ok(schema)
+    }
+
+    /// Whether the top level property may only be read, e.g. `Arn`
+    pub fn is_read_only(&self, name: &str) -> bool {
+        let pointer = format!("/properties/{name}");
+        self.read_only_properties.contains(&pointer)
+    }
+
+    /// How the property at the path can be set
+    pub fn access(&self, path: &[PathSegment<'_>]) -> PropertyAccess {
+        let mut pointer = String::from("/properties");
+        // a few schemas write nested deprecated properties as a JSON schema path instead, e.g.
+        // `/properties/DistributionConfig/properties/S3Origin`
+        let mut schema_pointer = String::new();
+        for segment in path {
+            pointer.push('/');
+            schema_pointer.push_str(match segment {
+                PathSegment::Key(_) => "/properties/",
+                PathSegment::Item => "/items",
+            });
+            match segment {
+                PathSegment::Key(name) => {
+                    pointer.push_str(name);
+                    schema_pointer.push_str(name);
+                }
+                PathSegment::Item => pointer.push('*'),
+            }
+        }
+        PropertyAccess {
+            create_only: self.create_only_properties.contains(&pointer),
+            write_only: self.write_only_properties.contains(&pointer),
+            read_only: self.read_only_properties.contains(&pointer),
+            deprecated: self.deprecated_properties.contains(&pointer)
+                || self.deprecated_properties.contains(&schema_pointer),
+        }
+    }
+
+    /// Attributes that can be retrieved with `Fn::GetAtt`, e.g. `Arn` or `Endpoint.Address`
+    pub fn attributes(&self) -> Vec<String> {
+        self.read_only_properties
+            .iter()
+            .filter_map(|property| crate::attribute_name(property))
+            .collect()
+    }
+
+    /// Follow any `$ref` pointers until reaching a concrete schema
+    ///
+    /// Pointers that cannot be resolved leave the schema as it is.
+    pub fn resolve<'s>(&'s self, mut schema: &'s PropertySchema) -> &'s PropertySchema {
+        for _ in 0..MAX_REFERENCE_DEPTH {
+            let Some(target) = schema.reference.as_deref().and_then(|r| self.pointer(r)) else {
+                break;
+            };
+            schema = target;
+        }
+        schema
+    }
+
+    /// Look up a JSON pointer within this schema, e.g. `#/definitions/Tag`
+    fn pointer(&self, reference: &str) -> Option<&PropertySchema> {
+        let mut segments = reference.strip_prefix("#/")?.split('/');
+        let mut schema = match (segments.next()?, segments.next()?) {
+            ("definitions", name) => self.definitions.get(name)?,
+            ("properties", name) => self.root.properties.get(name)?,
+            _ => return None,
+        };
+        while let Some(segment) = segments.next() {
+            schema = match segment {
+                "properties" => schema.properties.get(segments.next()?)?,
+                "items" => schema.items.as_deref()?,
+                _ => return None,
+            };
+        }
+        Some(schema)
+    }
+
+    /// The properties an object schema accepts, including those of any `oneOf`, `anyOf` or
+    /// `allOf` alternatives
+    ///
+    /// The property schemas are returned as declared, so that [`ResourceSchema::type_name`] can
+    /// describe them by the definition they refer to.
+    pub fn properties<'s>(
+        &'s self,
+        schema: &'s PropertySchema,
+    ) -> Vec<(&'s str, &'s PropertySchema)> {
+        let schema = self.resolve(schema);
+        let mut properties: Vec<_> = schema
+            .properties
+            .iter()
+            .map(|(name, property)| (name.as_str(), property))
+            .collect();
+        for alternative in schema
+            .one_of
+            .iter()
+            .chain(&schema.any_of)
+            .chain(&schema.all_of)
+        {
+            for (name, property) in self.properties(alternative) {
+                if !properties.iter().any(|(existing, _)| *existing == name) {
+                    properties.push((name, property));
+                }
+            }
+        }
+        properties
+    }
+
+    /// Schema of the named property of an object schema
+    pub fn property<'s>(
+        &'s self,
+        schema: &'s PropertySchema,
+        name: &str,
+    ) -> Option<&'s PropertySchema> {
+        if let Some((_, property)) = self
+            .properties(schema)
+            .into_iter()
+            .find(|(n, _)| *n == name)
+        {
+            return Some(self.resolve(property));
+        }
+        let schema = self.resolve(schema);
+        schema
+            .pattern_properties
+            .iter()
+            .find(|(pattern, _)| {
+                self.patterns
+                    .get(*pattern)
+                    .and_then(Option::as_ref)
+                    .is_some_and(|pattern| pattern.is_match(name))
+            })
+            .map(|(_, property)| self.resolve(property))
+    }
+
+    /// Whether the text matches the `pattern` of a schema within this document, or `None` if it
+    /// has no pattern or the pattern is not supported by the regex crate
+    pub fn matches_pattern(&self, schema: &PropertySchema, text: &str) -> Option<bool> {
+        let regex = self.patterns.get(schema.pattern.as_ref()?)?.as_ref()?;
+        Some(regex.is_match(text))
+    }
+
+    /// Schema of the items of an array schema
+    pub fn items<'s>(&'s self, schema: &'s PropertySchema) -> Option<&'s PropertySchema> {
+        let schema = self.resolve(schema);
+        schema.items.as_deref().map(|items| self.resolve(items))
+    }
+
+    /// Navigate from the resource properties to a nested schema
+    pub fn lookup(&self, path: &[PathSegment<'_>]) -> Option<&PropertySchema> {
+        path.iter()
+            .try_fold(&self.root, |schema, segment| match segment {
+                PathSegment::Key(name) => self.property(schema, name),
+                PathSegment::Item => self.items(schema),
+            })
+    }
+
+    /// Navigate using a dotted path with `[]` marking array items, e.g. `Policies[].PolicyDocument`
+    pub fn lookup_path(&self, path: &str) -> Option<&PropertySchema> {
+        let mut segments = Vec::new();
+        for part in path.split('.') {
+            let mut part = part;
+            let mut items = 0;
+            while let Some(stripped) = part.strip_suffix("[]") {
+                part = stripped;
+                items += 1;
+            }
+            segments.push(PathSegment::Key(part));
+            segments.extend(std::iter::repeat_n(PathSegment::Item, items));
+        }
+        self.lookup(&segments)
+    }
+
+    /// Human readable description of the type of a schema, e.g. `string` or `array<Tag>`
+    pub fn type_name(&self, schema: &PropertySchema) -> String {
+        if let Some(reference) = &schema.reference {
+            let name = reference.rsplit('/').next().unwrap_or(reference);
+            // references to primitive definitions are more useful described by their type
+            let resolved = self.resolve(schema);
+            if resolved.properties.is_empty() && !resolved.types.is_empty() {
+                let primitive = self.type_name(resolved);
+                if primitive != "object" {
+                    return primitive;
+                }
+            }
+            return name.to_string();
+        }
+        match schema.types.as_slice() {
+            [PrimitiveType::Array] => {
+                let items = schema
+                    .items
+                    .as_deref()
+                    .map_or_else(|| "any".to_string(), |items| self.type_name(items));
+                format!("array<{items}>")
+            }
+            [] => "any".to_string(),
+            types => types
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" | "),
+        }
+    }
+}
+
+/// Compile the `pattern` and `patternProperties` patterns of the schema and every schema nested
+/// in it
+fn compile_patterns(schema: &PropertySchema, patterns: &mut HashMap<String, Option<Regex>>) {
+    for pattern in schema
+        .pattern
+        .iter()
+        .chain(schema.pattern_properties.keys())
+    {
+        if !patterns.contains_key(pattern) {
+            patterns.insert(pattern.clone(), Regex::new(pattern).ok());
+        }
+    }
+    for property in schema.pattern_properties.values() {
+        compile_patterns(property, patterns);
+    }
+    let nested = schema
+        .properties
+        .values()
+        .chain(schema.items.as_deref())
+        .chain(&schema.one_of)
+        .chain(&schema.any_of)
+        .chain(&schema.all_of);
+    for property in nested {
+        compile_patterns(property, patterns);
+    }
+}
+
+/// Loaded schemas by resource type, with `None` for types that have no usable schema
+type SchemaCache = HashMap<String, Option<Arc<ResourceSchema>>>;
+
+static RESOURCE_SCHEMAS: OnceLock<Mutex<SchemaCache>> = OnceLock::new();
+
+/// Load the full schema for a resource type, e.g. `AWS::S3::Bucket`
+///
+/// Schemas are cached after they are first loaded, and so are unknown types, so that a typo in a
+/// template does not search the archive again on every analysis.
+pub fn resource_schema(resource_type: &str) -> Result<Arc<ResourceSchema>> {
+    let cache = RESOURCE_SCHEMAS.get_or_init(Default::default);
+    if let Some(schema) = cache.lock().unwrap().get(resource_type) {
+        return schema
+            .clone()
+            .ok_or_else(|| SchemaError::UnknownResourceType(resource_type.to_string()));
+    }
+
+    // failing to open the archive says nothing about the resource type, so it is not cached
+    let mut z = schema_archive()?;
+    let name = format!(
+        "{}.json",
+        resource_type.to_ascii_lowercase().replace("::", "-")
+    );
+    let schema = match z.by_name(&name) {
+        Ok(zf) => match ResourceSchema::from_reader(&name, zf) {
+            Ok(schema) => Some(Arc::new(schema)),
+            Err(e) => {
+                tracing::warn!(error = %e, %name, "error parsing resource schema");
+                None
+            }
+        },
+        Err(zip::result::ZipError::FileNotFound) => None,
+        Err(e) => return Err(SchemaError::ZipError(e)),
+    };
+    cache
+        .lock()
+        .unwrap()
+        .insert(resource_type.to_string(), schema.clone());
+    schema.ok_or_else(|| SchemaError::UnknownResourceType(resource_type.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iam_role() -> ResourceSchema {
+        let f = std::fs::File::open("testdata/aws-iam-role.json").unwrap();
+        ResourceSchema::from_reader("aws-iam-role.json", f).unwrap()
+    }
+
+    #[test]
+    fn parsing_resource_schema() {
+        let schema = iam_role();
+        assert_eq!(schema.type_name, "AWS::IAM::Role");
+        assert_eq!(schema.root.required, ["AssumeRolePolicyDocument"]);
+        assert_eq!(schema.root.additional_properties, Some(false));
+        assert!(schema.is_read_only("Arn"));
+        assert!(!schema.is_read_only("RoleName"));
+        assert_eq!(schema.attributes(), ["Arn", "RoleId"]);
+        assert_eq!(
+            schema.access(&[PathSegment::Key("RoleName")]),
+            PropertyAccess {
+                create_only: true,
+                ..Default::default()
+            }
+        );
+        assert!(schema.access(&[PathSegment::Key("Arn")]).read_only);
+        assert_eq!(
+            schema.access(&[PathSegment::Key("Description")]),
+            PropertyAccess::default()
+        );
+
+        let max_session = &schema.root.properties["MaxSessionDuration"];
+        assert_eq!(max_session.types, [PrimitiveType::Integer]);
+        assert_eq!(
+            schema.root.properties["AssumeRolePolicyDocument"].types,
+            [PrimitiveType::Object, PrimitiveType::String]
+        );
+    }
+
+    #[test]
+    fn navigating_references() {
+        let schema = iam_role();
+        let document = schema.lookup_path("Policies[].PolicyDocument").unwrap();
+        assert_eq!(
+            document.types,
+            [PrimitiveType::String, PrimitiveType::Object]
+        );
+
+        let policy = schema
+            .lookup(&[PathSegment::Key("Policies"), PathSegment::Item])
+            .unwrap();
+        let names: Vec<_> = schema
+            .properties(policy)
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(names, ["PolicyDocument", "PolicyName"]);
+        assert_eq!(policy.required, ["PolicyName", "PolicyDocument"]);
+
+        assert!(schema.lookup_path("Policies[].Missing").is_none());
+        assert!(schema.lookup_path("Missing").is_none());
+    }
+
+    #[test]
+    fn describing_types() {
+        let schema = iam_role();
+        let type_name = |path| schema.type_name(&schema.root.properties[path]);
+        assert_eq!(type_name("Policies"), "array<Policy>");
+        assert_eq!(type_name("ManagedPolicyArns"), "array<string>");
+        assert_eq!(type_name("AssumeRolePolicyDocument"), "object | string");
+    }
+
+    #[test]
+    fn loading_from_bundle() {
+        let schema = resource_schema("AWS::S3::Bucket").unwrap();
+        let rule = schema
+            .lookup_path("LifecycleConfiguration.Rules[].Transitions[].StorageClass")
+            .unwrap();
+        assert!(rule.enum_values.as_ref().is_some_and(|v| !v.is_empty()));
+        assert!(Arc::ptr_eq(
+            &schema,
+            &resource_schema("AWS::S3::Bucket").unwrap()
+        ));
+        assert!(resource_schema("Custom::Thing").is_err());
+    }
+
+    #[test]
+    fn deprecated_properties() {
+        let autoscaling = resource_schema("AWS::AutoScaling::AutoScalingGroup").unwrap();
+        assert!(
+            autoscaling
+                .access(&[PathSegment::Key("NotificationConfiguration")])
+                .deprecated
+        );
+        assert!(
+            !autoscaling
+                .access(&[PathSegment::Key("NotificationConfigurations")])
+                .deprecated
+        );
+
+        let security_group = resource_schema("AWS::EC2::SecurityGroup").unwrap();
+        let egress = [
+            PathSegment::Key("SecurityGroupEgress"),
+            PathSegment::Item,
+            PathSegment::Key("SourceSecurityGroupId"),
+        ];
+        assert!(security_group.access(&egress).deprecated);
+
+        let distribution = resource_schema("AWS::CloudFront::Distribution").unwrap();
+        let origin = [
+            PathSegment::Key("DistributionConfig"),
+            PathSegment::Key("S3Origin"),
+        ];
+        assert!(distribution.access(&origin).deprecated);
+    }
+
+    #[test]
+    fn pattern_properties() {
+        let json = r##"{
+            "typeName": "Test::Pattern::Thing",
+            "properties": {"Labels": {"$ref": "#/definitions/Labels"}},
+            "definitions": {
+                "Labels": {
+                    "type": "object",
+                    "patternProperties": {
+                        "^[a-z]+$": {"type": "string", "pattern": "^v[0-9]+$"},
+                        "(?<=X)1": {"type": "integer"}
+                    }
+                }
+            }
+        }"##;
+        let schema = ResourceSchema::from_reader("test.json", json.as_bytes()).unwrap();
+        let label = |name| schema.lookup(&[PathSegment::Key("Labels"), PathSegment::Key(name)]);
+        assert_eq!(label("team").unwrap().types, [PrimitiveType::String]);
+        assert!(label("Team").is_none());
+        // look-behind is not supported by the regex crate, so the pattern never matches
+        assert!(label("X1").is_none());
+        assert_eq!(schema.patterns.len(), 3);
+        let value = label("team").unwrap();
+        assert_eq!(schema.matches_pattern(value, "v2"), Some(true));
+        assert_eq!(schema.matches_pattern(value, "2"), Some(false));
+        assert_eq!(schema.matches_pattern(&schema.root, "2"), None);
+    }
+
+    #[test]
+    fn all_bundled_schemas_parse() {
+        let mut archive = schema_archive().unwrap();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).unwrap();
+            let name = file.name().to_string();
+            if name.ends_with(".json") {
+                ResourceSchema::from_reader(&name, file).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_resource_types_are_cached() {
+        let resource_type = "AWS::S3::Bukcet";
+        assert!(matches!(
+            resource_schema(resource_type),
+            Err(SchemaError::UnknownResourceType(_))
+        ));
+        let cache = RESOURCE_SCHEMAS.get().unwrap();
+        assert!(matches!(
+            cache.lock().unwrap().get(resource_type),
+            Some(None)
+        ));
+        assert!(matches!(
+            resource_schema(resource_type),
+            Err(SchemaError::UnknownResourceType(_))
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+//! Quick fixes for the diagnostics published by this server
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, NumberOrString, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::diagnostics::{DIAGNOSTIC_SOURCE, INVALID_ATTRIBUTE, Replacements};
+
+/// Quick fixes for the diagnostics the client sent with a `textDocument/codeAction` request
+///
+/// The replacements are carried in the `data` of each diagnostic, so the document does not need
+/// to be analysed again.
+pub(crate) fn quick_fixes(uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    for diagnostic in diagnostics {
+        if diagnostic.source.as_deref() != Some(DIAGNOSTIC_SOURCE)
+            || diagnostic.code != Some(NumberOrString::String(INVALID_ATTRIBUTE.to_string()))
+        {
+            continue;
+        }
+        let Some(Replacements { replacements }) = diagnostic
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value(data).ok())
+        else {
+            continue;
+        };
+        for (i, replacement) in replacements.into_iter().enumerate() {
+            let edit = TextEdit {
+                range: diagnostic.range,
+                new_text: replacement.clone(),
+            };
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Replace with `{replacement}`"),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                // the closest match is listed first
+                is_preferred: Some(i == 0),
+                ..Default::default()
+            }));
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    #[test]
+    fn replace_invalid_attribute() {
+        let document = test_document(
+            "stack.yml",
+            "yaml",
+            include_str!("../testdata/invalid_attributes.yml"),
+        );
+
+        let actions = quick_fixes(&document.uri, &document.diagnostics);
+        let titles: Vec<_> = actions
+            .iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => action.title.as_str(),
+                CodeActionOrCommand::Command(command) => command.title.as_str(),
+            })
+            .collect();
+        insta::assert_yaml_snapshot!(titles);
+
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        let edits = &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&document.uri];
+        assert_eq!(
+            edits[0].range,
+            action.diagnostics.as_ref().unwrap()[0].range
+        );
+    }
+}
@@ -0,0 +1,1218 @@
+//! Completion items offered at a position in a template
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, MarkupContent,
+    MarkupKind, Position, Range, TextEdit,
+};
+
+use crate::{
+    TemplateLanguage,
+    destinations::JumpDestinationType,
+    diagnostics::PSEUDO_PARAMETERS,
+    document::Document,
+    template::{Spanned, Template, Value, reparse_tree},
+};
+
+/// Compute the completion items for the cursor position, if the position is one we can complete
+pub(crate) fn completions(document: &Document, position: Position) -> Option<Vec<CompletionItem>> {
+    let line = document
+        .text
+        .lines()
+        .nth(position.line as usize)
+        .unwrap_or_default();
+
+    let prefix = match document.language {
+        TemplateLanguage::Yaml => "Type:",
+        TemplateLanguage::Json => "\"Type\":",
+    };
+    if line.trim_start().starts_with(prefix) {
+        return Some(resource_types());
+    }
+
+    if let Some(items) = getatt_attributes(document, position) {
+        return Some(items);
+    }
+
+    if let Some(items) = findinmap_arguments(document, position) {
+        return Some(items);
+    }
+
+    if let Some(items) = targets(document, position) {
+        return Some(items);
+    }
+
+    property_names(document, position)
+}
+
+fn resource_types() -> Vec<CompletionItem> {
+    cfn_lsp_schema::get_resource_types()
+        .iter()
+        .map(|resource| CompletionItem {
+            label: resource.type_name.clone(),
+            kind: Some(CompletionItemKind::CLASS),
+            documentation: resource.description.clone().map(Documentation::String),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// What a reference being typed may refer to
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum TargetKind {
+    /// Resources, parameters and pseudo parameters, for `Ref` and `${}` in a `Sub`
+    Ref,
+    /// Resources only, for the logical ID of a `GetAtt` and `DependsOn`
+    Resource,
+    /// Conditions, for `Fn::If` and `!Condition`
+    Condition,
+    /// Conditions, for a `Condition` key, which only names a condition as the attribute of a
+    /// resource or output or as the condition function inside a condition expression
+    ConditionKey,
+}
+
+/// Where the target of a reference is being typed
+#[derive(Debug, PartialEq)]
+struct TargetContext {
+    kind: TargetKind,
+    /// Byte offset of the start of the partially typed name, after any opening quote
+    name_start: usize,
+    /// Whether the name is being typed inside a quoted string
+    in_string: bool,
+}
+
+/// Offer the declared resources and parameters, and the pseudo parameters, that a reference
+/// being typed may refer to
+fn targets(document: &Document, position: Position) -> Option<Vec<CompletionItem>> {
+    let (line_start, column) = line_offsets(document, position);
+    let before = &document.text[..line_start + column];
+    let mut context = target_context(before, line_start)?;
+    if context.kind == TargetKind::ConditionKey {
+        if !is_condition_key(&document.template, position.line as usize) {
+            return None;
+        }
+        context.kind = TargetKind::Condition;
+    }
+
+    let typed = &before[context.name_start..];
+    let range = Range {
+        start: Position::new(
+            position.line,
+            position.character - typed.encode_utf16().count() as u32,
+        ),
+        end: position,
+    };
+    let quote = document.language == TemplateLanguage::Json && !context.in_string;
+    let item = |label: &str, kind, detail: Option<String>| {
+        let new_text = if quote {
+            format!("\"{label}\"")
+        } else {
+            label.to_string()
+        };
+        CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            detail,
+            filter_text: Some(new_text.clone()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+            ..Default::default()
+        }
+    };
+
+    let template = &document.template;
+    let mut items = Vec::new();
+    for destination in &document.jump_destinations {
+        match destination.r#type {
+            JumpDestinationType::Condition if context.kind == TargetKind::Condition => {
+                items.push(item(
+                    &destination.name,
+                    CompletionItemKind::VALUE,
+                    Some("Condition".to_string()),
+                ));
+            }
+            JumpDestinationType::Resource if context.kind != TargetKind::Condition => {
+                let resource_type = template
+                    .resources
+                    .iter()
+                    .flat_map(|section| &section.entries)
+                    .find(|resource| resource.name.value == destination.name)
+                    .and_then(|resource| resource.resource_type.as_ref())
+                    .map(|t| t.value.clone());
+                items.push(item(
+                    &destination.name,
+                    CompletionItemKind::STRUCT,
+                    resource_type,
+                ));
+            }
+            JumpDestinationType::Parameter if context.kind == TargetKind::Ref => {
+                let parameter_type = template
+                    .parameters
+                    .iter()
+                    .flat_map(|section| &section.entries)
+                    .find(|parameter| parameter.name.value == destination.name)
+                    .and_then(|parameter| parameter.parameter_type.as_ref())
+                    .map(|t| format!("Parameter ({})", t.value));
+                items.push(item(
+                    &destination.name,
+                    CompletionItemKind::VARIABLE,
+                    parameter_type.or(Some("Parameter".to_string())),
+                ));
+            }
+            _ => {}
+        }
+    }
+    if context.kind == TargetKind::Ref {
+        for name in PSEUDO_PARAMETERS {
+            items.push(item(
+                name,
+                CompletionItemKind::CONSTANT,
+                Some("Pseudo parameter".to_string()),
+            ));
+        }
+    }
+    Some(items)
+}
+
+/// Work out whether the text before the cursor ends in the target of a `Ref`, `GetAtt`,
+/// `DependsOn`, `${}` placeholder or condition reference
+///
+/// `line_start` is the byte offset of the start of the cursor's line.
+fn target_context(before: &str, line_start: usize) -> Option<TargetContext> {
+    let name = before
+        .trim_end_matches(|c: char| is_key_char(c) || c == ':')
+        .len();
+    let head = &before[..name];
+    let (head, in_string) = match head.strip_suffix(['"', '\'']) {
+        Some(head) => (head, true),
+        None => (head, false),
+    };
+    let context = |kind| {
+        Some(TargetContext {
+            kind,
+            name_start: name,
+            in_string,
+        })
+    };
+
+    if head.ends_with("${") {
+        return context(TargetKind::Ref);
+    }
+    let line = &head[line_start.min(head.len())..];
+    let has_space = line.ends_with([' ', '\t']);
+    // JSON keys are quoted, so compare without quotes
+    let line = line.trim_end().replace('"', "");
+    if (has_space && line.ends_with("!Ref")) || ends_with_key(&line, "Ref:") {
+        return context(TargetKind::Ref);
+    }
+    if (has_space && line.ends_with("!GetAtt"))
+        || line.ends_with("!GetAtt [")
+        || line.ends_with("GetAtt: [")
+    {
+        return context(TargetKind::Resource);
+    }
+    if ends_with_key(&line, "Condition:") {
+        return context(TargetKind::ConditionKey);
+    }
+    if (has_space && line.ends_with("!Condition"))
+        || line.ends_with("!If [")
+        || line.ends_with("If: [")
+    {
+        return context(TargetKind::Condition);
+    }
+    if let Some((_, rest)) = line.rsplit_once("DependsOn:") {
+        let rest = rest.trim_start();
+        let in_list = rest.strip_prefix('[').is_some_and(|items| {
+            (items.is_empty() || items.ends_with(','))
+                && items
+                    .chars()
+                    .all(|c| is_key_char(c) || c == ',' || c == ' ')
+        });
+        if rest.is_empty() || in_list {
+            return context(TargetKind::Resource);
+        }
+    }
+
+    // items of a block sequence, under `DependsOn:` or as the first item of `Fn::GetAtt:`
+    if line.trim() == "-" {
+        let mut items = 0;
+        for previous in before[..line_start].lines().rev() {
+            let previous = previous.trim().replace('"', "");
+            if previous.is_empty() {
+                continue;
+            }
+            if previous.starts_with("- ") {
+                items += 1;
+                continue;
+            }
+            if previous.ends_with("DependsOn:") || (items == 0 && previous.ends_with("GetAtt:")) {
+                return context(TargetKind::Resource);
+            }
+            if items == 0 && (previous.ends_with("Fn::If:") || previous.ends_with("!If")) {
+                return context(TargetKind::Condition);
+            }
+            break;
+        }
+    }
+    None
+}
+
+/// Whether the line ends with the whole key, rather than a longer key that ends with the same
+/// text, e.g. `Ref:` but not `VpcRef:`
+fn ends_with_key(line: &str, key: &str) -> bool {
+    line.strip_suffix(key)
+        .is_some_and(|head| head.is_empty() || head.ends_with([' ', '\t', '{', ',', '-']))
+}
+
+/// Whether the `Condition` key on the line names a template condition, which it does as the
+/// attribute of a resource or output and inside the expressions of the `Conditions` section,
+/// but not e.g. in the `Condition` block of an IAM policy statement
+fn is_condition_key(template: &Template, line: usize) -> bool {
+    let is_key = |key: &Spanned<String>| key.value == "Condition" && key.span.start.line == line;
+    let is_attribute = |definition: &Spanned<Value>| {
+        definition
+            .value
+            .as_mapping()
+            .unwrap_or_default()
+            .iter()
+            .any(|(key, _)| is_key(key))
+    };
+    template
+        .resources
+        .iter()
+        .flat_map(|section| &section.entries)
+        .any(|resource| is_attribute(&resource.definition))
+        || template
+            .outputs
+            .iter()
+            .flat_map(|section| &section.entries)
+            .any(|output| is_attribute(&output.definition))
+        || template
+            .conditions
+            .iter()
+            .flat_map(|section| &section.entries)
+            .any(|condition| has_key(&condition.definition, &is_key))
+}
+
+/// Whether any mapping within the value has a key matching the predicate
+fn has_key(value: &Spanned<Value>, predicate: &impl Fn(&Spanned<String>) -> bool) -> bool {
+    match &value.value {
+        Value::Mapping(pairs) => pairs
+            .iter()
+            .any(|(key, value)| predicate(key) || has_key(value, predicate)),
+        Value::Sequence(items) => items.iter().any(|item| has_key(item, predicate)),
+        _ => false,
+    }
+}
+
+/// Offer the attributes of the resource named in a `GetAtt` whose attribute is being typed
+fn getatt_attributes(document: &Document, position: Position) -> Option<Vec<CompletionItem>> {
+    let (line_start, column) = line_offsets(document, position);
+    let before = &document.text[..line_start + column];
+    let context = getatt_context(before)?;
+
+    let resource = document
+        .template
+        .resources
+        .as_ref()?
+        .entries
+        .iter()
+        .find(|r| r.name.value == context.logical_id)?;
+    let resource_type = resource.resource_type.as_ref()?;
+    let schema = match cfn_lsp_schema::resource_schema(&resource_type.value) {
+        Ok(schema) => schema,
+        Err(e) => {
+            tracing::debug!(error = %e, resource_type = %resource_type.value, "no schema for resource type");
+            return None;
+        }
+    };
+
+    // replace everything typed so far, as attribute names may contain dots
+    let typed = &before[context.attribute_start..];
+    let range = Range {
+        start: Position::new(
+            position.line,
+            position.character - typed.encode_utf16().count() as u32,
+        ),
+        end: position,
+    };
+    let items = schema
+        .attributes()
+        .into_iter()
+        .map(|name| {
+            let property = schema.lookup_path(&name);
+            let new_text = if document.language == TemplateLanguage::Json && !context.in_string {
+                format!("\"{name}\"")
+            } else {
+                name.clone()
+            };
+            CompletionItem {
+                detail: property.map(|p| schema.type_name(p)),
+                documentation: property
+                    .and_then(|p| {
+                        p.description
+                            .as_ref()
+                            .or(schema.resolve(p).description.as_ref())
+                    })
+                    .map(|description| {
+                        Documentation::MarkupContent(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: description.clone(),
+                        })
+                    }),
+                kind: Some(CompletionItemKind::FIELD),
+                filter_text: Some(new_text.clone()),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+                label: name,
+                ..Default::default()
+            }
+        })
+        .collect();
+    Some(items)
+}
+
+/// Where the attribute of a `GetAtt` is being typed
+#[derive(Debug, PartialEq)]
+struct GetAttContext<'a> {
+    logical_id: &'a str,
+    /// Byte offset of the start of the partially typed attribute, after any opening quote
+    attribute_start: usize,
+    /// Whether the attribute is being typed inside a quoted string
+    in_string: bool,
+}
+
+/// Work out whether the text before the cursor ends in the attribute of a `GetAtt`, in any of
+/// `!GetAtt Name.Attr`, `[Name, Attr]` or a block sequence with `Name` and `Attr` as items
+fn getatt_context(before: &str) -> Option<GetAttContext<'_>> {
+    let index = before.rfind("GetAtt")?;
+    let after = index + "GetAtt".len();
+    // skip the closing quote of the JSON key and the separator
+    let rest = before[after..].trim_start_matches(['"', ':']);
+    let trimmed = rest.trim_start();
+    let offset = before.len() - trimmed.len();
+
+    let (logical_id, attribute) = if let Some(list) = trimmed.strip_prefix('[') {
+        let (logical_id, attribute) = list.split_once(',')?;
+        (logical_id.trim(), attribute.trim_start())
+    } else if trimmed.starts_with('-') {
+        let mut items = trimmed.split('\n');
+        let logical_id = items.next()?.strip_prefix('-')?.trim();
+        let attribute = items.next()?.trim_start().strip_prefix('-')?.trim_start();
+        if items.next().is_some() {
+            return None;
+        }
+        (logical_id, attribute)
+    } else if before[after..].starts_with([' ', '\t']) {
+        // the short form `!GetAtt Name.Attr`, which must be on one line
+        if before[after..offset].contains('\n') {
+            return None;
+        }
+        trimmed.split_once('.')?
+    } else {
+        return None;
+    };
+
+    let logical_id = logical_id.trim_matches(|c| c == '"' || c == '\'');
+    let in_string = attribute.starts_with(['"', '\'']);
+    let attribute = attribute.trim_start_matches(['"', '\'']);
+    if logical_id.is_empty()
+        || !logical_id.chars().all(is_key_char)
+        || !attribute.chars().all(|c| is_key_char(c) || c == '.')
+    {
+        return None;
+    }
+    Some(GetAttContext {
+        logical_id,
+        attribute_start: before.len() - attribute.len(),
+        in_string,
+    })
+}
+
+/// Offer the mapping names and keys for the argument of a `FindInMap` being typed
+fn findinmap_arguments(document: &Document, position: Position) -> Option<Vec<CompletionItem>> {
+    let (line_start, column) = line_offsets(document, position);
+    let before = &document.text[..line_start + column];
+    let context = findinmap_context(before)?;
+
+    let typed = &before[context.argument_start..];
+    let range = Range {
+        start: Position::new(
+            position.line,
+            position.character - typed.encode_utf16().count() as u32,
+        ),
+        end: position,
+    };
+    let quote = document.language == TemplateLanguage::Json && !context.in_string;
+    let item = |label: &str, kind, detail: String| {
+        let new_text = if quote {
+            format!("\"{label}\"")
+        } else {
+            label.to_string()
+        };
+        CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            detail: Some(detail),
+            filter_text: Some(new_text.clone()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+            ..Default::default()
+        }
+    };
+
+    let template = &document.template;
+    let items = match context.arguments[..] {
+        [] => template
+            .mappings
+            .iter()
+            .flat_map(|section| &section.entries)
+            .map(|mapping| {
+                item(
+                    &mapping.name.value,
+                    CompletionItemKind::MODULE,
+                    "Mapping".to_string(),
+                )
+            })
+            .collect(),
+        [Some(name)] => template
+            .mapping(name)?
+            .top_level_keys()
+            .map(|key| {
+                item(
+                    &key.value,
+                    CompletionItemKind::PROPERTY,
+                    format!("Top level key of {name}"),
+                )
+            })
+            .collect(),
+        // keys computed by a nested function could be any of the top level keys
+        [Some(name), top_level_key] => {
+            let mut items: Vec<CompletionItem> = Vec::new();
+            for key in template.mapping(name)?.second_level_keys(top_level_key) {
+                if items.iter().all(|item| item.label != key.value) {
+                    items.push(item(
+                        &key.value,
+                        CompletionItemKind::PROPERTY,
+                        format!("Second level key of {name}"),
+                    ));
+                }
+            }
+            items
+        }
+        _ => return None,
+    };
+    Some(items)
+}
+
+/// Where an argument of a `FindInMap` is being typed
+#[derive(Debug, PartialEq)]
+struct FindInMapContext<'a> {
+    /// The arguments before the one being typed, or `None` for those computed by nested functions
+    arguments: Vec<Option<&'a str>>,
+    /// Byte offset of the start of the partially typed argument, after any opening quote
+    argument_start: usize,
+    /// Whether the argument is being typed inside a quoted string
+    in_string: bool,
+}
+
+/// Work out whether the text before the cursor ends in an argument of a `FindInMap`, in either
+/// the `[Map, Key, Key]` form or a block sequence with the arguments as items
+fn findinmap_context(before: &str) -> Option<FindInMapContext<'_>> {
+    let index = before.rfind("FindInMap")?;
+    let after = index + "FindInMap".len();
+    // skip the closing quote of the JSON key and the separator
+    let rest = before[after..].trim_start_matches(['"', ':']);
+    let trimmed = rest.trim_start();
+
+    let mut arguments: Vec<_> = if let Some(list) = trimmed.strip_prefix('[') {
+        if list.contains(']') {
+            return None;
+        }
+        list.split(',').collect()
+    } else if trimmed.starts_with('-') {
+        let mut items = Vec::new();
+        for line in trimmed.split('\n') {
+            items.push(line.trim_start().strip_prefix('-')?);
+        }
+        items
+    } else {
+        return None;
+    };
+    let argument = arguments.pop()?.trim_start();
+    if arguments.len() > 2 {
+        return None;
+    }
+
+    let in_string = argument.starts_with(['"', '\'']);
+    let argument = argument.trim_start_matches(['"', '\'']);
+    if !argument.chars().all(is_map_key_char) {
+        return None;
+    }
+    let arguments = arguments
+        .into_iter()
+        .map(|argument| {
+            let argument = argument.trim().trim_matches(|c| c == '"' || c == '\'');
+            // nested functions, e.g. `!Ref AWS::Region` or `{"Ref": "AWS::Region"}`
+            Some(argument).filter(|argument| argument.chars().all(is_map_key_char))
+        })
+        .collect();
+    Some(FindInMapContext {
+        arguments,
+        argument_start: before.len() - argument.len(),
+        in_string,
+    })
+}
+
+/// Offer the schema properties of the resource whose `Properties` block contains the cursor
+fn property_names(document: &Document, position: Position) -> Option<Vec<CompletionItem>> {
+    let context = key_context(document, position)?;
+    let [section, logical_id, properties] = context.path.as_slice() else {
+        return None;
+    };
+    if section != "Resources" || properties != "Properties" {
+        return None;
+    }
+
+    // the partially typed key usually leaves the template unparseable, so analyse the template
+    // with it removed
+    let language = document.language;
+    let mut text = document.text.clone();
+    text.replace_range(context.partial.clone(), &" ".repeat(context.partial.len()));
+    let tree = reparse_tree(&text, language, None).ok()?;
+    let template = Template::from_tree(&tree, &text, language);
+    let resource = template
+        .resources
+        .as_ref()?
+        .entries
+        .iter()
+        .find(|r| &r.name.value == logical_id)?;
+    let resource_type = resource.resource_type.as_ref()?;
+    let existing: Vec<&str> = resource
+        .properties
+        .as_ref()
+        .and_then(|p| p.value.as_mapping())
+        .unwrap_or_default()
+        .iter()
+        .map(|(key, _)| key.value.as_str())
+        .collect();
+
+    let schema = match cfn_lsp_schema::resource_schema(&resource_type.value) {
+        Ok(schema) => schema,
+        Err(e) => {
+            tracing::debug!(error = %e, resource_type = %resource_type.value, "no schema for resource type");
+            return None;
+        }
+    };
+
+    let items = schema
+        .properties(&schema.root)
+        .into_iter()
+        .filter(|(name, _)| !existing.contains(name))
+        .filter(|(name, _)| !schema.is_read_only(name))
+        .map(|(name, property)| {
+            let required = schema.root.required.iter().any(|r| r == name);
+            let type_name = schema.type_name(property);
+            let mut documentation = format!(
+                "**Type**: `{type_name}`\n\n**Required**: {}",
+                if required { "yes" } else { "no" }
+            );
+            if let Some(description) = property
+                .description
+                .as_ref()
+                .or(schema.resolve(property).description.as_ref())
+            {
+                documentation.push_str("\n\n");
+                documentation.push_str(description);
+            }
+            let insert_text = match (language, context.in_string) {
+                (TemplateLanguage::Json, false) => Some(format!("\"{name}\"")),
+                _ => None,
+            };
+            CompletionItem {
+                detail: Some(if required {
+                    format!("{type_name} (required)")
+                } else {
+                    type_name.to_string()
+                }),
+                documentation: Some(Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: documentation,
+                })),
+                kind: Some(CompletionItemKind::PROPERTY),
+                // list the required properties first
+                sort_text: Some(format!("{}{}", if required { 0 } else { 1 }, name)),
+                insert_text,
+                label: name.to_string(),
+                ..Default::default()
+            }
+        })
+        .collect();
+    Some(items)
+}
+
+/// Where a mapping key is being typed
+#[derive(Debug, PartialEq)]
+struct KeyContext {
+    /// Keys of the mappings enclosing the cursor, outermost first
+    path: Vec<String>,
+    /// Byte range of the partially typed key, including any opening quote
+    partial: std::ops::Range<usize>,
+    /// Whether the cursor is inside a quoted JSON string
+    in_string: bool,
+}
+
+/// Work out whether the cursor is in the position of a mapping key, and if so which mappings
+/// enclose it
+///
+/// This works on the raw text rather than the syntax tree because the half typed key usually
+/// leaves the document unparseable.
+fn key_context(document: &Document, position: Position) -> Option<KeyContext> {
+    let (line_start, column) = line_offsets(document, position);
+    let text = document.text.as_str();
+    match document.language {
+        TemplateLanguage::Yaml => yaml_key_context(text, line_start, column),
+        TemplateLanguage::Json => json_key_context(text, line_start + column),
+    }
+}
+
+/// Byte offsets of the start of the cursor's line, and of the cursor within that line
+fn line_offsets(document: &Document, position: Position) -> (usize, usize) {
+    let column = document.text_position(position).col;
+    (document.byte_offset(position) - column, column)
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Mapping keys are often region names or other identifiers with separators, e.g. `us-east-1`
+fn is_map_key_char(c: char) -> bool {
+    is_key_char(c) || matches!(c, '-' | '.' | ':')
+}
+
+fn yaml_key_context(text: &str, line_start: usize, column: usize) -> Option<KeyContext> {
+    let line = text[line_start..].lines().next().unwrap_or_default();
+    let before = &line[..column];
+    let typed = before.trim_start();
+    if !typed.chars().all(is_key_char) {
+        return None;
+    }
+    let indent = before.len() - typed.len();
+    if indent == 0 {
+        return None;
+    }
+    let typed_after = line[column..]
+        .find(|c| !is_key_char(c))
+        .unwrap_or(line.len() - column);
+
+    // walk upwards collecting each line that is less indented than the previous key
+    let mut path = Vec::new();
+    let mut current_indent = indent;
+    for line in text[..line_start].lines().rev() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        let line_indent = line.len() - content.len();
+        if line_indent >= current_indent {
+            continue;
+        }
+        // keys nested inside sequences are not supported
+        let (key, rest) = content.split_once(':')?;
+        let rest = rest.trim_start();
+        if content.starts_with('-') || !(rest.is_empty() || rest.starts_with('#')) {
+            return None;
+        }
+        path.push(
+            key.trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string(),
+        );
+        current_indent = line_indent;
+        if current_indent == 0 {
+            break;
+        }
+    }
+    if current_indent != 0 {
+        return None;
+    }
+    path.reverse();
+
+    Some(KeyContext {
+        path,
+        partial: line_start + indent..line_start + column + typed_after,
+        in_string: false,
+    })
+}
+
+fn json_key_context(text: &str, offset: usize) -> Option<KeyContext> {
+    struct Frame {
+        key: Option<String>,
+        is_object: bool,
+    }
+
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut expect_key = false;
+    let mut last_string = None;
+    let mut pending_key = None;
+    let mut string: Option<(usize, String)> = None;
+    let mut escaped = false;
+
+    for (i, c) in text[..offset].char_indices() {
+        if let Some((_, contents)) = string.as_mut() {
+            if escaped {
+                escaped = false;
+                contents.push(c);
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                last_string = Some(std::mem::take(contents));
+                string = None;
+            } else {
+                contents.push(c);
+            }
+            continue;
+        }
+        match c {
+            '"' => string = Some((i, String::new())),
+            ':' => {
+                pending_key = last_string.take();
+                expect_key = false;
+            }
+            '{' | '[' => {
+                stack.push(Frame {
+                    key: pending_key.take(),
+                    is_object: c == '{',
+                });
+                expect_key = c == '{';
+            }
+            '}' | ']' => {
+                stack.pop();
+                expect_key = false;
+                pending_key = None;
+            }
+            ',' => {
+                expect_key = stack.last().is_some_and(|frame| frame.is_object);
+                pending_key = None;
+                last_string = None;
+            }
+            _ => {}
+        }
+    }
+
+    if !expect_key {
+        return None;
+    }
+    let (partial_start, in_string) = match &string {
+        Some((start, _)) => (*start, true),
+        None => (offset, false),
+    };
+    // include the rest of the key and its closing quote
+    let rest = &text[offset..];
+    let mut partial_end = offset + rest.find(|c| !is_key_char(c)).unwrap_or(rest.len());
+    if in_string && text[partial_end..].starts_with('"') {
+        partial_end += 1;
+    }
+
+    // the root object has no key, and keys nested inside arrays are not supported
+    let mut frames = stack.into_iter();
+    frames.next()?;
+    let path = frames
+        .map(|frame| frame.key.filter(|_| frame.is_object))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(KeyContext {
+        path,
+        partial: partial_start..partial_end,
+        in_string,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Split the template at the `|` marking the cursor
+    fn cursor(template: &str) -> (String, Position) {
+        let offset = template.find('|').unwrap();
+        let before = &template[..offset];
+        let line = before.matches('\n').count() as u32;
+        let character = before.rsplit('\n').next().unwrap().encode_utf16().count() as u32;
+        (
+            template.replacen('|', "", 1),
+            Position::new(line, character),
+        )
+    }
+
+    fn labels(template: &str, language: TemplateLanguage) -> Option<Vec<String>> {
+        let language_id = match language {
+            TemplateLanguage::Yaml => "yaml",
+            TemplateLanguage::Json => "json",
+        };
+        let (document, position) = document(template, language_id);
+        let items = property_names(&document, position)?;
+        Some(items.into_iter().map(|item| item.label).collect())
+    }
+
+    #[test]
+    fn yaml_key_paths() {
+        let template = "Resources:\n  Role:\n    Type: AWS::IAM::Role\n    Properties:\n\n      # comment\n      Ro|le\n";
+        let (doc, position) = document(template, "yaml");
+        let context = key_context(&doc, position).unwrap();
+        assert_eq!(context.path, ["Resources", "Role", "Properties"]);
+        assert_eq!(&doc.text[context.partial], "Role");
+
+        for template in [
+            "Resources:\n  Role:\n    Type: AWS::IAM::Role\n    Properties:\n      RoleName: fo|o\n",
+            "Resources:\n  Role:\n    Type: AWS::IAM::Role\n    Properties:\n      Tags:\n        - Ke|\n",
+            "Resou|\n",
+        ] {
+            let (doc, position) = document(template, "yaml");
+            assert_eq!(key_context(&doc, position), None);
+        }
+    }
+
+    #[test]
+    fn json_key_paths() {
+        let template = r#"{"Resources": {"Role": {"Type": "AWS::IAM::Role", "Properties": {"Path": "/", "Ro|le"}}}}"#;
+        let (doc, position) = document(template, "json");
+        let context = key_context(&doc, position).unwrap();
+        assert_eq!(context.path, ["Resources", "Role", "Properties"]);
+        assert_eq!(&doc.text[context.partial], "\"Role\"");
+        assert!(context.in_string);
+
+        let template = "{\"Resources\": {\"Role\": {\"Properties\": {\n|\n}}}}";
+        let (doc, position) = document(template, "json");
+        let context = key_context(&doc, position).unwrap();
+        assert_eq!(context.path, ["Resources", "Role", "Properties"]);
+        assert!(!context.in_string);
+
+        for template in [
+            r#"{"Resources": {"Role": {"Properties": {"Path": "|"}}}}"#,
+            r#"{"Resources": {"Role": {"Properties": {"Tags": [{"|"}]}}}}"#,
+        ] {
+            let (doc, position) = document(template, "json");
+            assert_eq!(key_context(&doc, position), None);
+        }
+    }
+
+    #[test]
+    fn yaml_property_names() {
+        let template = "Resources:\n  Role:\n    Type: AWS::IAM::Role\n    Properties:\n      Path: /\n      |\n      Description: role\n";
+        insta::assert_yaml_snapshot!(labels(template, TemplateLanguage::Yaml));
+    }
+
+    #[test]
+    fn yaml_property_names_while_typing() {
+        let template = "Resources:\n  Role:\n    Properties:\n      Path: /\n      Ro|\n      Description: role\n    Type: AWS::IAM::Role\n";
+        let labels = labels(template, TemplateLanguage::Yaml).unwrap();
+        assert!(labels.contains(&"RoleName".to_string()));
+        assert!(!labels.contains(&"Path".to_string()));
+    }
+
+    #[test]
+    fn json_property_names() {
+        let template = r#"{"Resources": {"Role": {"Type": "AWS::IAM::Role", "Properties": {"Path": "/", "|", "Description": "role"}}}}"#;
+        insta::assert_yaml_snapshot!(labels(template, TemplateLanguage::Json));
+    }
+
+    #[test]
+    fn required_properties_are_listed_first() {
+        let (document, position) = document(
+            "Resources:\n  Role:\n    Type: AWS::IAM::Role\n    Properties:\n      |\n",
+            "yaml",
+        );
+        let mut items = property_names(&document, position).unwrap();
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        assert_eq!(items[0].label, "AssumeRolePolicyDocument");
+        assert_eq!(
+            items[0].detail.as_deref(),
+            Some("object | string (required)")
+        );
+    }
+
+    #[test]
+    fn unknown_resource_type() {
+        let template = "Resources:\n  Thing:\n    Type: Custom::Thing\n    Properties:\n      |\n";
+        assert_eq!(labels(template, TemplateLanguage::Yaml), None);
+    }
+
+    fn document(template: &str, language_id: &str) -> (Document, Position) {
+        let (text, position) = cursor(template);
+        (test_document("stack", language_id, &text), position)
+    }
+
+    #[test]
+    fn getatt_contexts() {
+        for (before, logical_id, typed) in [
+            ("Value: !GetAtt Bucket.", "Bucket", ""),
+            ("Value: !GetAtt Bucket.Dual", "Bucket", "Dual"),
+            ("Value: !GetAtt Bucket.Endpoint.Ad", "Bucket", "Endpoint.Ad"),
+            ("Value: !GetAtt [Bucket, Ar", "Bucket", "Ar"),
+            ("Value:\n  Fn::GetAtt: [\"Bucket\", ", "Bucket", ""),
+            (
+                "Value:\n  Fn::GetAtt:\n    - Bucket\n    - A",
+                "Bucket",
+                "A",
+            ),
+            ("\"Fn::GetAtt\": [\"Bucket\", \"Ar", "Bucket", "Ar"),
+            ("\"Fn::GetAtt\": [\n  \"Bucket\",\n  ", "Bucket", ""),
+        ] {
+            let context = getatt_context(before).unwrap();
+            assert_eq!(context.logical_id, logical_id, "{before}");
+            assert_eq!(&before[context.attribute_start..], typed, "{before}");
+        }
+
+        for before in [
+            "Value: !GetAtt Buck",
+            "Value: !GetAtt [Bucket, Arn]\nOther: foo",
+            "Value: !GetAtt Bucket.Arn\n  Name: ",
+            "Value: !GetAtt\nBucket.",
+            "Value:\n  Fn::GetAtt:\n    - Bucket\n    - Arn\n  Other:\n    - A",
+        ] {
+            assert_eq!(getatt_context(before), None, "{before}");
+        }
+    }
+
+    #[test]
+    fn yaml_getatt_attributes() {
+        let (document, position) = document(
+            "Resources:\n  Database:\n    Type: AWS::RDS::DBInstance\nOutputs:\n  Address:\n    Value: !GetAtt Database.Endpoint.|\n",
+            "yaml",
+        );
+        let items = completions(&document, position).unwrap();
+        let address = items
+            .iter()
+            .find(|item| item.label == "Endpoint.Address")
+            .unwrap();
+        let Some(CompletionTextEdit::Edit(edit)) = &address.text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.range.start, Position::new(5, 28));
+        assert_eq!(edit.range.end, position);
+        assert!(items.iter().any(|item| item.label == "DBInstanceArn"));
+    }
+
+    #[test]
+    fn json_getatt_attributes() {
+        let (document, position) = document(
+            r#"{"Resources": {"Topic": {"Type": "AWS::SNS::Topic"}}, "Outputs": {"Name": {"Value": {"Fn::GetAtt": ["Topic", |]}}}}"#,
+            "json",
+        );
+        let items = completions(&document, position).unwrap();
+        let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+        insta::assert_yaml_snapshot!(labels);
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.new_text, format!("\"{}\"", items[0].label));
+    }
+
+    #[test]
+    fn findinmap_contexts() {
+        for (before, arguments, typed) in [
+            ("Value: !FindInMap [", vec![], ""),
+            ("Value: !FindInMap [Region", vec![], "Region"),
+            (
+                "Value: !FindInMap [RegionMap, us-e",
+                vec![Some("RegionMap")],
+                "us-e",
+            ),
+            (
+                "Value: !FindInMap [RegionMap, !Ref AWS::Region, A",
+                vec![Some("RegionMap"), None],
+                "A",
+            ),
+            (
+                "Value:\n  Fn::FindInMap:\n    - RegionMap\n    - us-east-1\n    - ",
+                vec![Some("RegionMap"), Some("us-east-1")],
+                "",
+            ),
+            (
+                "\"Fn::FindInMap\": [\"RegionMap\", {\"Ref\": \"AWS::Region\"}, \"A",
+                vec![Some("RegionMap"), None],
+                "A",
+            ),
+        ] {
+            let context = findinmap_context(before).unwrap();
+            assert_eq!(context.arguments, arguments, "{before}");
+            assert_eq!(&before[context.argument_start..], typed, "{before}");
+        }
+
+        for before in [
+            "Value: !FindInMap RegionMap",
+            "Value: !FindInMap [RegionMap, us-east-1, AMI]\nOther: ",
+            "Value: !FindInMap [RegionMap, us-east-1, AMI, ",
+            "Value:\n  Fn::FindInMap:\n    - RegionMap\n  Other: A",
+        ] {
+            assert_eq!(findinmap_context(before), None, "{before}");
+        }
+    }
+
+    #[test]
+    fn findinmap_keys() {
+        let mappings = "Mappings:\n  RegionMap:\n    us-east-1:\n      AMI: ami-1\n      Size: small\n    us-west-2:\n      AMI: ami-2\n      Arch: arm64\n";
+        let labels = |value: &str| {
+            let (document, position) = document(
+                &format!(
+                    "{mappings}Resources:\n  Instance:\n    Type: AWS::EC2::Instance\n    Properties:\n      ImageId: {value}\n"
+                ),
+                "yaml",
+            );
+            let items = completions(&document, position).unwrap();
+            items.into_iter().map(|item| item.label).collect::<Vec<_>>()
+        };
+
+        assert_eq!(labels("!FindInMap [|]"), ["RegionMap"]);
+        assert_eq!(
+            labels("!FindInMap [RegionMap, |]"),
+            ["us-east-1", "us-west-2"]
+        );
+        assert_eq!(
+            labels("!FindInMap [RegionMap, us-west-2, |]"),
+            ["AMI", "Arch"]
+        );
+        assert_eq!(
+            labels("!FindInMap [RegionMap, !Ref AWS::Region, |]"),
+            ["AMI", "Size", "Arch"]
+        );
+    }
+
+    #[test]
+    fn json_findinmap_keys() {
+        let (document, position) = document(
+            r#"{"Mappings": {"RegionMap": {"us-east-1": {"AMI": "ami-1"}}}, "Outputs": {"Ami": {"Value": {"Fn::FindInMap": ["RegionMap", |]}}}}"#,
+            "json",
+        );
+        let items = completions(&document, position).unwrap();
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.new_text, "\"us-east-1\"");
+    }
+
+    #[test]
+    fn target_contexts() {
+        for (before, kind, typed) in [
+            ("Value: !Ref ", TargetKind::Ref, ""),
+            ("Value: !Ref Buck", TargetKind::Ref, "Buck"),
+            ("Value:\n  Ref: AWS::Re", TargetKind::Ref, "AWS::Re"),
+            ("\"Value\": {\"Ref\": \"Buc", TargetKind::Ref, "Buc"),
+            ("Value:\n  - Ref: Buc", TargetKind::Ref, "Buc"),
+            ("    Condition: Is", TargetKind::ConditionKey, "Is"),
+            (
+                "Value: !Sub \"arn:${AWS::Partition}:s3:::${Bu",
+                TargetKind::Ref,
+                "Bu",
+            ),
+            ("Value: !GetAtt Buck", TargetKind::Resource, "Buck"),
+            ("Value: !GetAtt [", TargetKind::Resource, ""),
+            ("Value:\n  Fn::GetAtt: [Buc", TargetKind::Resource, "Buc"),
+            ("\"Fn::GetAtt\": [\"", TargetKind::Resource, ""),
+            ("    DependsOn: Qu", TargetKind::Resource, "Qu"),
+            ("    DependsOn: [Queue, To", TargetKind::Resource, "To"),
+            (
+                "    \"DependsOn\": [\"Queue\", \"",
+                TargetKind::Resource,
+                "",
+            ),
+            (
+                "    DependsOn:\n      - Queue\n      - T",
+                TargetKind::Resource,
+                "T",
+            ),
+            (
+                "Value:\n  Fn::GetAtt:\n    - Buc",
+                TargetKind::Resource,
+                "Buc",
+            ),
+        ] {
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            let context = target_context(before, line_start).unwrap();
+            assert_eq!(context.kind, kind, "{before}");
+            assert_eq!(&before[context.name_start..], typed, "{before}");
+        }
+
+        for before in [
+            "Value: !Refs ",
+            "Value: !GetAtt Bucket.",
+            "    DependsOn: [Queue] ",
+            "Value:\n  Fn::GetAtt:\n    - Bucket\n    - A",
+            "Tags:\n  - K",
+            "Value: !If [IsProduction, ",
+            "    Condition:\n      Str",
+            "    VpcRef: ",
+            "    SecurityGroupRef: Sg",
+            "\"SecurityGroupRef\": \"",
+            "    MyCondition: ",
+        ] {
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            assert_eq!(target_context(before, line_start), None, "{before}");
+        }
+    }
+
+    #[test]
+    fn ref_targets() {
+        let (document, position) = document(
+            "Parameters:\n  Environment:\n    Type: String\nResources:\n  Topic:\n    Type: AWS::SNS::Topic\n  Queue:\n    Type: AWS::SQS::Queue\n    Properties:\n      QueueName: !Ref |\n",
+            "yaml",
+        );
+        let items = completions(&document, position).unwrap();
+        let items: Vec<_> = items
+            .iter()
+            .map(|item| (item.label.as_str(), item.detail.as_deref()))
+            .collect();
+        insta::assert_yaml_snapshot!(items);
+    }
+
+    #[test]
+    fn getatt_targets_are_resources() {
+        let (document, position) = document(
+            r#"{"Parameters": {"Environment": {"Type": "String"}}, "Resources": {"Topic": {"Type": "AWS::SNS::Topic"}}, "Outputs": {"Arn": {"Value": {"Fn::GetAtt": [|]}}}}"#,
+            "json",
+        );
+        let items = completions(&document, position).unwrap();
+        assert_eq!(items.len(), 1);
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.new_text, "\"Topic\"");
+    }
+
+    #[test]
+    fn condition_targets() {
+        let (document, position) = document(
+            "Conditions:\n  IsProduction: !Equals [!Ref AWS::Region, us-east-1]\nResources:\n  Topic:\n    Type: AWS::SNS::Topic\n    Condition: |\n",
+            "yaml",
+        );
+        let items = completions(&document, position).unwrap();
+        let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, ["IsProduction"]);
+    }
+
+    #[test]
+    fn condition_targets_in_condition_expressions() {
+        let (yaml, position) = document(
+            "Conditions:\n  IsProduction: !Equals [!Ref AWS::Region, us-east-1]\n  IsEurope:\n    Fn::And:\n      - Condition: |\n",
+            "yaml",
+        );
+        let items = completions(&yaml, position).unwrap();
+        let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+        assert!(labels.contains(&"IsProduction"), "{labels:?}");
+
+        let (json, position) = document(
+            r#"{"Conditions": {"IsProduction": {"Fn::Equals": [{"Ref": "AWS::Region"}, "us-east-1"]}}, "Outputs": {"Arn": {"Value": "x", "Condition": "|"}}}"#,
+            "json",
+        );
+        let items = completions(&json, position).unwrap();
+        let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, ["IsProduction"]);
+    }
+
+    #[test]
+    fn iam_policy_conditions_are_not_condition_targets() {
+        let (document, position) = document(
+            "Conditions:\n  IsProduction: !Equals [!Ref AWS::Region, us-east-1]\nResources:\n  Role:\n    Type: AWS::IAM::Role\n    Properties:\n      AssumeRolePolicyDocument:\n        Statement:\n          - Effect: Allow\n            Condition: |\n",
+            "yaml",
+        );
+        let items = completions(&document, position).unwrap_or_default();
+        assert!(items.iter().all(|item| item.label != "IsProduction"));
+    }
+}
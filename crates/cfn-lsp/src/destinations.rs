@@ -1,193 +1,82 @@
-use std::collections::HashMap;
+use crate::template::{Section, Spanned, Template};
 
-use anyhow::Context;
-use serde::Deserialize;
-use tower_lsp::lsp_types::Position as LspPosition;
-use tower_lsp::lsp_types::Range;
-use yaml_rust::YamlLoader;
-
-/// State of the parser
-#[derive(Default)]
-enum State {
-    #[default]
-    Init,
-    ParsingResources,
-    ParsingOutputs,
-    ParsingParameters,
-    ParsingMappings,
-    ParsingConditions,
-    // TODO: other template top level fields
-}
+pub(crate) use crate::template::Span;
 
 /// Container type allowing access to the different reference types
 /// * definitions (for go to definition)
 /// * (future) completions
 /// * (future) references (for references to jump targets)
-pub struct Destinations<'s> {
-    /// Reference to the raw template
-    content: &'s str,
-    /// Internal state of the parser
-    state: State,
-}
-
-macro_rules! parse_line {
-    ($line:ident, $line_number:expr, $parsed_structure:ident, $jump_type:expr, $field:ident => $destinations:ident, false) => {{
-        let sanitised_line = $line.trim().replace(":", "");
-        let values = &$parsed_structure.$field;
-        if values.contains_key(&sanitised_line) {
-            let span = span_from_line($line_number, $line, &sanitised_line)
-                .context("constructing span")?;
-            let dest = JumpDestination {
-                name: sanitised_line.to_string(),
-                r#type: $jump_type,
-                span,
-            };
-            $destinations.push(dest);
-        }
-    }};
-    ($line:ident, $line_number:expr, $parsed_structure:ident, $jump_type:expr, $field:ident => $destinations:ident, true) => {{
-        let sanitised_line = $line.trim().replace(":", "");
-        if let Some(values) = &$parsed_structure.$field {
-            if values.contains_key(&sanitised_line) {
-                let span = span_from_line($line_number, $line, &sanitised_line)
-                    .context("constructing span")?;
-                let dest = JumpDestination {
-                    name: sanitised_line.to_string(),
-                    r#type: $jump_type,
-                    span,
-                };
-                $destinations.push(dest);
-            }
-        }
-    }};
+pub struct Destinations<'t> {
+    /// Reference to the parsed template
+    template: &'t Template,
 }
 
-impl<'s> Destinations<'s> {
-    pub fn new(content: &'s str) -> Self {
-        Self {
-            content,
-            state: State::default(),
-        }
+impl<'t> Destinations<'t> {
+    pub fn new(template: &'t Template) -> Self {
+        Self { template }
     }
 
-    pub fn definitions(&mut self) -> anyhow::Result<Vec<JumpDestination>> {
-        let parsed_structure = self.raw_parse().context("parsing template")?;
-
+    pub fn definitions(&self) -> Vec<JumpDestination> {
         let mut destinations = Vec::new();
 
-        let _parsed_template = YamlLoader::load_from_str(self.content).expect("loading the yaml");
-
-        for (line_number, line) in self.content.lines().enumerate() {
-            let trimmed_line = line.trim();
-            if trimmed_line == "Resources:" {
-                self.state = State::ParsingResources;
-                continue;
-            } else if trimmed_line == "Outputs:" {
-                self.state = State::ParsingOutputs;
-                continue;
-            } else if trimmed_line == "Mappings:" {
-                self.state = State::ParsingMappings;
-                continue;
-            } else if trimmed_line == "Parameters:" {
-                self.state = State::ParsingParameters;
-                continue;
-            } else if trimmed_line == "Conditions:" {
-                self.state = State::ParsingConditions;
-                continue;
-            }
-
-            // we are not opening a new section
-            match self.state {
-                State::Init => {}
-                State::ParsingResources => {
-                    parse_line!(line, line_number, parsed_structure, JumpDestinationType::Resource, resources => destinations, false);
-                }
-                State::ParsingOutputs => {
-                    parse_line!(line, line_number, parsed_structure, JumpDestinationType::Output, outputs => destinations, true)
-                }
-                State::ParsingParameters => {
-                    parse_line!(line, line_number, parsed_structure, JumpDestinationType::Parameter, parameters => destinations, true);
-                }
-                State::ParsingMappings => {
-                    parse_line!(line, line_number, parsed_structure, JumpDestinationType::Mapping, mappings => destinations, true);
-                }
-                State::ParsingConditions => {
-                    parse_line!(line, line_number, parsed_structure, JumpDestinationType::Condition, conditions => destinations, true);
-                }
-            }
-        }
-        Ok(destinations)
+        let template = self.template;
+        push_section(
+            &mut destinations,
+            &template.resources,
+            JumpDestinationType::Resource,
+            |r| &r.name,
+        );
+        push_section(
+            &mut destinations,
+            &template.outputs,
+            JumpDestinationType::Output,
+            |o| &o.name,
+        );
+        push_section(
+            &mut destinations,
+            &template.parameters,
+            JumpDestinationType::Parameter,
+            |p| &p.name,
+        );
+        push_section(
+            &mut destinations,
+            &template.mappings,
+            JumpDestinationType::Mapping,
+            |m| &m.name,
+        );
+        push_section(
+            &mut destinations,
+            &template.conditions,
+            JumpDestinationType::Condition,
+            |c| &c.name,
+        );
+
+        // report the destinations in document order
+        destinations.sort_by_key(|d| d.span.start);
+        destinations
     }
-
-    fn raw_parse(&self) -> anyhow::Result<Template> {
-        serde_yaml::from_str(self.content).context("parsing template")
-    }
-}
-
-fn span_from_line(line_number: usize, line: &str, target: &str) -> anyhow::Result<Span> {
-    for i in 0..(line.len() - target.len()) {
-        if &line[i..(i + target.len())] == target {
-            return Ok(Span {
-                start: Position {
-                    line: line_number,
-                    col: i,
-                },
-                end: Position {
-                    line: line_number,
-                    col: i + target.len() - 1,
-                },
-            });
-        }
-    }
-
-    Err(anyhow::anyhow!("programming error"))
 }
 
-#[derive(Deserialize)]
-struct Template {
-    #[serde(rename = "Resources")]
-    resources: HashMap<String, serde_yaml::Value>,
-    #[serde(rename = "Outputs")]
-    outputs: Option<HashMap<String, serde_yaml::Value>>,
-    #[serde(rename = "Mappings")]
-    mappings: Option<HashMap<String, serde_yaml::Value>>,
-    #[serde(rename = "Parameters")]
-    parameters: Option<HashMap<String, serde_yaml::Value>>,
-    #[serde(rename = "Conditions")]
-    conditions: Option<HashMap<String, serde_yaml::Value>>,
-}
-
-#[derive(Debug)]
-pub struct Position {
-    line: usize,
-    col: usize,
-}
-
-impl Position {
-    fn to_lsp_position(&self) -> LspPosition {
-        LspPosition {
-            line: u32::try_from(self.line).expect("line larger than 32 bit integer"),
-            character: u32::try_from(self.col).expect("column larger than 32 bit integer"),
+fn push_section<T>(
+    destinations: &mut Vec<JumpDestination>,
+    section: &Option<Section<T>>,
+    r#type: JumpDestinationType,
+    name: impl Fn(&T) -> &Spanned<String>,
+) {
+    let Some(section) = section else {
+        return;
+    };
+    destinations.extend(section.entries.iter().map(|entry| {
+        let name = name(entry);
+        JumpDestination {
+            name: name.value.clone(),
+            r#type,
+            span: name.span,
         }
-    }
+    }));
 }
 
-#[derive(Debug)]
-pub struct Span {
-    pub start: Position,
-    pub end: Position,
-}
-
-impl Span {
-    pub fn to_range(&self) -> Range {
-        Range {
-            start: self.start.to_lsp_position(),
-            end: self.end.to_lsp_position(),
-        }
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum JumpDestinationType {
     Resource,
     Parameter,
@@ -209,53 +98,85 @@ mod tests {
 
     mod definitions {
         use super::*;
+        use crate::TemplateLanguage;
+
+        fn definitions(contents: &str) -> Vec<JumpDestination> {
+            let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+            Destinations::new(&template).definitions()
+        }
+
+        fn json_definitions(contents: &str) -> Vec<JumpDestination> {
+            let template = Template::parse(contents, TemplateLanguage::Json).unwrap();
+            Destinations::new(&template).definitions()
+        }
 
         #[test]
         fn parse_simple() {
             let contents = include_str!("../testdata/simple.yml");
-            let mut destinations = Destinations::new(contents);
-            let targets = destinations.definitions();
+            let targets = definitions(contents);
             insta::assert_debug_snapshot!(targets);
         }
 
         #[test]
         fn parse_two_resources() {
             let contents = include_str!("../testdata/two_resources.yml");
-            let mut destinations = Destinations::new(contents);
-            let targets = destinations.definitions();
+            let targets = definitions(contents);
             insta::assert_debug_snapshot!(targets);
         }
 
         #[test]
         fn parse_with_outputs() {
             let contents = include_str!("../testdata/outputs.yml");
-            let mut destinations = Destinations::new(contents);
-            let targets = destinations.definitions();
+            let targets = definitions(contents);
             insta::assert_debug_snapshot!(targets);
         }
 
         #[test]
         fn parse_parameters() {
             let contents = include_str!("../testdata/parameters.yml");
-            let mut destinations = Destinations::new(contents);
-            let targets = destinations.definitions();
+            let targets = definitions(contents);
             insta::assert_debug_snapshot!(targets);
         }
 
         #[test]
         fn parse_conditions() {
             let contents = include_str!("../testdata/if.yml");
-            let mut destinations = Destinations::new(contents);
-            let targets = destinations.definitions();
+            let targets = definitions(contents);
             insta::assert_debug_snapshot!(targets);
         }
 
         #[test]
         fn parse_mappings() {
             let contents = include_str!("../testdata/findinmap.yml");
-            let mut destinations = Destinations::new(contents);
-            let targets = destinations.definitions();
+            let targets = definitions(contents);
             insta::assert_debug_snapshot!(targets);
         }
+
+        #[test]
+        fn parse_json() {
+            let contents = include_str!("../testdata/template.json");
+            let targets = json_definitions(contents);
+            insta::assert_debug_snapshot!(targets);
+        }
+
+        #[test]
+        fn parse_json_sections() {
+            let contents = include_str!("../testdata/sections.json");
+            let targets = json_definitions(contents);
+            insta::assert_debug_snapshot!(targets);
+        }
+
+        #[test]
+        fn json_matches_yaml() {
+            let yaml = definitions(include_str!("../testdata/sections.yml"));
+            let json = json_definitions(include_str!("../testdata/sections.json"));
+            let names = |destinations: &[JumpDestination]| {
+                destinations
+                    .iter()
+                    .map(|d| (d.name.clone(), d.r#type))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(names(&yaml), names(&json));
+        }
     }
 }
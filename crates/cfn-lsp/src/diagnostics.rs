@@ -0,0 +1,456 @@
+//! Diagnostics computed by comparing the extracted references against the template definitions
+use ropey::Rope;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use crate::{
+    destinations::{JumpDestination, JumpDestinationType},
+    document::lsp_range,
+    references::{Literal, Reference, ReferenceType},
+    template::Template,
+};
+
+/// Name reported as the source of every diagnostic published by this server
+pub(crate) const DIAGNOSTIC_SOURCE: &str = "cfn-lsp";
+
+/// Code of the diagnostic for a `GetAtt` of an attribute the resource type does not have
+pub(crate) const INVALID_ATTRIBUTE: &str = "invalid-attribute";
+
+/// Most replacements offered for a misspelled name
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Names that may replace the range of a diagnostic, stored in its `data` so the quick fixes can
+/// be offered without recomputing the analysis
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Replacements {
+    pub(crate) replacements: Vec<String>,
+}
+
+/// Pseudo parameters that CloudFormation defines for every template
+pub(crate) const PSEUDO_PARAMETERS: &[&str] = &[
+    "AWS::AccountId",
+    "AWS::NotificationARNs",
+    "AWS::NoValue",
+    "AWS::Partition",
+    "AWS::Region",
+    "AWS::StackId",
+    "AWS::StackName",
+    "AWS::URLSuffix",
+];
+
+/// Find every reference whose target is not declared in the template
+///
+/// The ranges cover the name only, excluding any quotes, in the text of `rope`.
+pub(crate) fn unresolved_references(
+    rope: &Rope,
+    references: &[Reference],
+    destinations: &[JumpDestination],
+) -> Vec<Diagnostic> {
+    let is_declared = |target: &str, types: &[JumpDestinationType]| {
+        destinations
+            .iter()
+            .any(|d| d.name == target && types.contains(&d.r#type))
+    };
+
+    let mut diagnostics = Vec::new();
+    for reference in references {
+        let (kind, target, expected) = match &reference.typ {
+            ReferenceType::Ref(r) => (
+                "Ref",
+                &r.target,
+                &[
+                    JumpDestinationType::Resource,
+                    JumpDestinationType::Parameter,
+                ][..],
+            ),
+            ReferenceType::GetAtt(g) => ("GetAtt", &g.target, &[JumpDestinationType::Resource][..]),
+            ReferenceType::FindInMap(f) => {
+                ("FindInMap", &f.target, &[JumpDestinationType::Mapping][..])
+            }
+            ReferenceType::If(i) => ("If", &i.target, &[JumpDestinationType::Condition][..]),
+            ReferenceType::Condition(c) => (
+                "Condition",
+                &c.target,
+                &[JumpDestinationType::Condition][..],
+            ),
+            ReferenceType::DependsOn(d) => {
+                ("DependsOn", &d.target, &[JumpDestinationType::Resource][..])
+            }
+            // variables declared in the variable map of the two argument form always resolve
+            ReferenceType::Sub(s) if s.local.is_some() => continue,
+            ReferenceType::Sub(s) if s.attribute.is_some() => {
+                ("Sub", &s.target, &[JumpDestinationType::Resource][..])
+            }
+            ReferenceType::Sub(s) => (
+                "Sub",
+                &s.target,
+                &[
+                    JumpDestinationType::Resource,
+                    JumpDestinationType::Parameter,
+                ][..],
+            ),
+        };
+
+        let target = target.as_str();
+
+        let may_be_pseudo_parameter = match &reference.typ {
+            ReferenceType::Ref(_) => true,
+            ReferenceType::Sub(s) => s.attribute.is_none(),
+            _ => false,
+        };
+        if may_be_pseudo_parameter && PSEUDO_PARAMETERS.contains(&target) {
+            continue;
+        }
+
+        if is_declared(target, expected) {
+            continue;
+        }
+
+        diagnostics.push(Diagnostic {
+            range: lsp_range(rope, reference.span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message: format!(
+                "{kind} target `{target}` is not a declared {}",
+                describe(expected)
+            ),
+            ..Default::default()
+        });
+    }
+
+    diagnostics
+}
+
+/// Writable properties that `Fn::GetAtt` also returns, which the schemas do not list among the
+/// read only properties
+const WRITABLE_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("AWS::EC2::Instance", &["AvailabilityZone"]),
+    ("AWS::EC2::SecurityGroup", &["VpcId"]),
+    (
+        "AWS::EC2::Subnet",
+        &[
+            "AvailabilityZone",
+            "AvailabilityZoneId",
+            "CidrBlock",
+            "OutpostArn",
+            "VpcId",
+        ],
+    ),
+    ("AWS::EC2::VPC", &["CidrBlock"]),
+    ("AWS::SNS::Topic", &["TopicName"]),
+    ("AWS::SQS::Queue", &["QueueName"]),
+];
+
+/// Find every `GetAtt` (and `${Resource.Attribute}` in a `Sub`) of an attribute that the
+/// resource's type does not return
+pub(crate) fn invalid_attributes(
+    rope: &Rope,
+    references: &[Reference],
+    template: &Template,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for reference in references {
+        let (target, attribute) = match &reference.typ {
+            ReferenceType::GetAtt(g) => (&g.target, &g.attribute),
+            ReferenceType::Sub(s) => (&s.target, &s.attribute),
+            _ => continue,
+        };
+        let Some(Literal {
+            name: attribute,
+            span,
+        }) = attribute
+        else {
+            continue;
+        };
+
+        let Some(resource_type) = template
+            .resources
+            .iter()
+            .flat_map(|section| &section.entries)
+            .find(|resource| resource.name.value == *target)
+            .and_then(|resource| resource.resource_type.as_ref())
+        else {
+            continue;
+        };
+        // the outputs of nested stacks and the data returned by custom resources are only known
+        // once the stack is deployed
+        if matches!(
+            resource_type.value.as_str(),
+            "AWS::CloudFormation::Stack" | "AWS::CloudFormation::CustomResource"
+        ) {
+            continue;
+        }
+        let Ok(schema) = cfn_lsp_schema::resource_schema(&resource_type.value) else {
+            continue;
+        };
+        let mut attributes = schema.attributes();
+        // some schemas do not declare their read only properties at all
+        if attributes.is_empty() {
+            continue;
+        }
+        attributes.extend(
+            WRITABLE_ATTRIBUTES
+                .iter()
+                .filter(|(name, _)| *name == resource_type.value)
+                .flat_map(|(_, names)| names.iter().map(|name| name.to_string())),
+        );
+        if attributes.contains(attribute) {
+            continue;
+        }
+
+        let mut scored: Vec<_> = attributes
+            .iter()
+            .map(|candidate| (similarity(attribute, candidate), candidate))
+            .filter(|(score, _)| *score > 0.7)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let replacements = Replacements {
+            replacements: scored
+                .into_iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|(_, candidate)| candidate.clone())
+                .collect(),
+        };
+
+        let mut message = format!(
+            "`{attribute}` is not an attribute of `{}`",
+            resource_type.value
+        );
+        if !replacements.replacements.is_empty() {
+            let names: Vec<_> = replacements
+                .replacements
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect();
+            message.push_str(&format!(", did you mean {}?", names.join(" or ")));
+        }
+        diagnostics.push(Diagnostic {
+            range: lsp_range(rope, *span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(INVALID_ATTRIBUTE.to_string())),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message,
+            data: serde_json::to_value(&replacements).ok(),
+            ..Default::default()
+        });
+    }
+    diagnostics
+}
+
+/// Find every literal `Fn::FindInMap` key that does not exist in its mapping
+///
+/// Keys computed by nested functions, such as `!Ref AWS::Region`, are only known at deploy time
+/// and are not checked. References to undeclared mappings are reported as unresolved instead.
+pub(crate) fn invalid_map_keys(
+    rope: &Rope,
+    references: &[Reference],
+    template: &Template,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut push = |key: &Literal, message: String| {
+        diagnostics.push(Diagnostic {
+            range: lsp_range(rope, key.span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message,
+            ..Default::default()
+        })
+    };
+    for reference in references {
+        let ReferenceType::FindInMap(f) = &reference.typ else {
+            continue;
+        };
+        let Some(mapping) = template.mapping(&f.target) else {
+            continue;
+        };
+        if let Some(top_level_key) = &f.top_level_key
+            && !mapping
+                .top_level_keys()
+                .any(|key| key.value == top_level_key.name)
+        {
+            push(
+                top_level_key,
+                format!(
+                    "Mapping `{}` has no top level key `{}`",
+                    f.target, top_level_key.name
+                ),
+            );
+            // the second level key cannot be checked against a missing top level key
+            continue;
+        }
+        let Some(second_level_key) = &f.second_level_key else {
+            continue;
+        };
+        let top_level_key = f.top_level_key.as_ref().map(|key| key.name.as_str());
+        if !mapping
+            .second_level_keys(top_level_key)
+            .any(|key| key.value == second_level_key.name)
+        {
+            let message = match top_level_key {
+                Some(top_level_key) => format!(
+                    "Mapping `{}` has no second level key `{}` under `{top_level_key}`",
+                    f.target, second_level_key.name
+                ),
+                None => format!(
+                    "Mapping `{}` has no second level key `{}`",
+                    f.target, second_level_key.name
+                ),
+            };
+            push(second_level_key, message);
+        }
+    }
+    diagnostics
+}
+
+/// How similar a misspelled name is to a candidate, from 0 to 1
+fn similarity(name: &str, candidate: &str) -> f64 {
+    let score = strsim::jaro_winkler(name, candidate);
+    // abbreviations of the full name, e.g. `Url` for `QueueUrl`
+    if candidate
+        .to_ascii_lowercase()
+        .contains(&name.to_ascii_lowercase())
+    {
+        score.max(0.9)
+    } else {
+        score
+    }
+}
+
+fn describe(types: &[JumpDestinationType]) -> &'static str {
+    match types {
+        [
+            JumpDestinationType::Resource,
+            JumpDestinationType::Parameter,
+        ] => "resource, parameter or pseudo parameter",
+        [JumpDestinationType::Resource] => "resource",
+        [JumpDestinationType::Mapping] => "mapping",
+        [JumpDestinationType::Condition] => "condition",
+        _ => "symbol",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+    use crate::{
+        TemplateLanguage, destinations::Destinations, references::references, template::Template,
+    };
+
+    fn diagnostics_for(contents: &str) -> Vec<Diagnostic> {
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+        let destinations = Destinations::new(&template).definitions();
+        let references = references(&template, contents);
+        unresolved_references(&Rope::from_str(contents), &references, &destinations)
+    }
+
+    #[test]
+    fn resolved_templates_have_no_diagnostics() {
+        for contents in [
+            include_str!("../testdata/outputs.yml"),
+            include_str!("../testdata/parameters.yml"),
+            include_str!("../testdata/two_resources.yml"),
+            include_str!("../testdata/getatt.yml"),
+            include_str!("../testdata/findinmap.yml"),
+            include_str!("../testdata/dependson.yml"),
+            include_str!("../testdata/if.yml"),
+            include_str!("../testdata/subs.yml"),
+            include_str!("../testdata/rename.yml"),
+            include_str!("../testdata/sub_syntaxes.yml"),
+            include_str!("../testdata/conditions.yml"),
+        ] {
+            assert_eq!(diagnostics_for(contents), Vec::new());
+        }
+    }
+
+    #[test]
+    fn resolved_json_template_has_no_diagnostics() {
+        for contents in [
+            include_str!("../testdata/template.json"),
+            include_str!("../testdata/sections.json"),
+            include_str!("../testdata/sub_syntaxes.json"),
+            include_str!("../testdata/conditions.json"),
+        ] {
+            let template = Template::parse(contents, TemplateLanguage::Json).unwrap();
+            let destinations = Destinations::new(&template).definitions();
+            let references = references(&template, contents);
+            assert_eq!(
+                unresolved_references(&Rope::from_str(contents), &references, &destinations),
+                Vec::new()
+            );
+        }
+    }
+
+    #[test]
+    fn unresolved_targets() {
+        let contents = include_str!("../testdata/unresolved.yml");
+        insta::assert_yaml_snapshot!(diagnostics_for(contents));
+    }
+
+    #[test]
+    fn unresolved_ranges_exclude_quotes() {
+        let contents = "Resources:\n  Topic:\n    Type: AWS::SNS::Topic\n    Properties:\n      \
+                        DisplayName: !Sub \"${Missing}-alerts\"\n      \
+                        TopicName: !Ref \"Unknown\"\n";
+        let ranges: Vec<_> = diagnostics_for(contents)
+            .into_iter()
+            .map(|diagnostic| diagnostic.range)
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                Range::new(Position::new(4, 27), Position::new(4, 34)),
+                Range::new(Position::new(5, 23), Position::new(5, 30)),
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_attributes() {
+        for contents in [
+            include_str!("../testdata/getatt.yml"),
+            include_str!("../testdata/sub_syntaxes.yml"),
+            include_str!("../testdata/rename.yml"),
+        ] {
+            let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+            let references = references(&template, contents);
+            assert_eq!(
+                invalid_attributes(&Rope::from_str(contents), &references, &template),
+                Vec::new()
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_attribute_names() {
+        let contents = include_str!("../testdata/invalid_attributes.yml");
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+        let references = references(&template, contents);
+        insta::assert_yaml_snapshot!(invalid_attributes(
+            &Rope::from_str(contents),
+            &references,
+            &template
+        ));
+    }
+
+    #[test]
+    fn map_keys() {
+        let contents = include_str!("../testdata/findinmap.yml");
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+        let sources = references(&template, contents);
+        assert_eq!(
+            invalid_map_keys(&Rope::from_str(contents), &sources, &template),
+            Vec::new()
+        );
+
+        let contents = include_str!("../testdata/invalid_map_keys.yml");
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+        let sources = references(&template, contents);
+        insta::assert_yaml_snapshot!(invalid_map_keys(
+            &Rope::from_str(contents),
+            &sources,
+            &template
+        ));
+    }
+}
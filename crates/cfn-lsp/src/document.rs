@@ -0,0 +1,456 @@
+//! Open documents and the results of analysing them
+use ropey::Rope;
+use tower_lsp::lsp_types::{
+    Diagnostic, Position, Range, TextDocumentContentChangeEvent, TextDocumentItem, Url,
+};
+use tree_sitter::{InputEdit, Node, Point, Tree};
+
+use crate::{
+    TemplateLanguage,
+    destinations::{Destinations, JumpDestination},
+    detect_template_language, diagnostics,
+    references::{Reference, references},
+    template::{self, Span, Template, reparse_tree},
+    validation,
+    workspace::{self, IndexedSymbol},
+};
+
+/// A template open in the editor, along with everything derived from its text
+pub(crate) struct Document {
+    pub(crate) uri: Url,
+    /// Language identifier sent by the client when the document was opened
+    pub(crate) language_id: String,
+    pub(crate) version: i32,
+    pub(crate) language: TemplateLanguage,
+    /// Current contents of the document, kept up to date with every edit
+    rope: Rope,
+    /// Syntax tree of the last analysis, edited alongside the rope so it can be reparsed
+    /// incrementally
+    tree: Option<Tree>,
+    /// Number of edits applied since the document was opened
+    generation: u64,
+    /// Generation the analysis results below were computed from
+    analysed_generation: Option<u64>,
+    /// Text the analysis results below were computed from
+    pub(crate) text: String,
+    pub(crate) template: Template,
+    pub(crate) jump_destinations: Vec<JumpDestination>,
+    pub(crate) jump_sources: Vec<Reference>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// Symbols the document adds to `workspace/symbol` results, empty if it is not a template
+    pub(crate) workspace_symbols: Vec<IndexedSymbol>,
+}
+
+impl Document {
+    pub(crate) fn new(item: TextDocumentItem) -> Self {
+        let language = TemplateLanguage::from_language_id(&item.language_id)
+            .unwrap_or_else(|| detect_template_language(item.uri.path(), &item.text));
+        let mut document = Self {
+            uri: item.uri,
+            language_id: item.language_id,
+            version: item.version,
+            language,
+            rope: Rope::from_str(&item.text),
+            tree: None,
+            generation: 0,
+            analysed_generation: None,
+            text: item.text,
+            template: Template::default(),
+            jump_destinations: Vec::new(),
+            jump_sources: Vec::new(),
+            diagnostics: Vec::new(),
+            workspace_symbols: Vec::new(),
+        };
+        document.analyse();
+        document
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Apply the changes sent by the client in a `textDocument/didChange` notification
+    ///
+    /// The analysis is not recomputed; call [`Document::ensure_analysed`] before using it.
+    pub(crate) fn apply_changes(
+        &mut self,
+        changes: impl IntoIterator<Item = TextDocumentContentChangeEvent>,
+        version: i32,
+    ) {
+        for change in changes {
+            match change.range {
+                Some(range) => self.apply_edit(range.start, range.end, &change.text),
+                None => {
+                    self.rope = Rope::from_str(&change.text);
+                    self.tree = None;
+                }
+            }
+        }
+        self.version = version;
+        self.generation += 1;
+    }
+
+    fn apply_edit(&mut self, start: Position, end: Position, text: &str) {
+        let start_char = self.char_index(start);
+        let end_char = self.char_index(end).max(start_char);
+
+        let start_byte = self.rope.char_to_byte(start_char);
+        let old_end_byte = self.rope.char_to_byte(end_char);
+        let start_position = self.point(start_byte);
+        let old_end_position = self.point(old_end_byte);
+
+        self.rope.remove(start_char..end_char);
+        self.rope.insert(start_char, text);
+
+        let new_end_byte = start_byte + text.len();
+        let new_end_position = self.point(new_end_byte);
+
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte,
+                old_end_byte,
+                new_end_byte,
+                start_position,
+                old_end_position,
+                new_end_position,
+            });
+        }
+    }
+
+    /// Convert an LSP position (UTF-16 code units) into a char index into the rope, clamping
+    /// positions past the end of a line or the document
+    fn char_index(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+        let line_start = self.rope.line_to_char(line);
+        let text = self.rope.line(line);
+        let mut length = text.len_chars();
+        while length > 0 && matches!(text.char(length - 1), '\n' | '\r') {
+            length -= 1;
+        }
+        let line_end = line_start + length;
+        let line_start_utf16 = self.rope.char_to_utf16_cu(line_start);
+        let target = line_start_utf16 + position.character as usize;
+        let line_end_utf16 = self.rope.char_to_utf16_cu(line_end);
+        self.rope.utf16_cu_to_char(target.min(line_end_utf16))
+    }
+
+    /// Convert a byte offset into the rope into a tree-sitter point
+    fn point(&self, byte: usize) -> Point {
+        let row = self.rope.byte_to_line(byte);
+        Point {
+            row,
+            column: byte - self.rope.line_to_byte(row),
+        }
+    }
+
+    /// Recompute the analysis if the document has changed since it was last analysed
+    pub(crate) fn ensure_analysed(&mut self) {
+        if self.analysed_generation != Some(self.generation) {
+            self.analyse();
+        }
+    }
+
+    /// Recompute the jump destinations, jump sources and diagnostics from the document text
+    fn analyse(&mut self) {
+        self.analysed_generation = Some(self.generation);
+        self.text = self.rope.to_string();
+        let contents = self.text.as_str();
+        let language = self.language;
+        tracing::debug!(uri = %self.uri, ?language, language_id = %self.language_id, "analysing document");
+
+        let tree = match reparse_tree(contents, language, self.tree.as_ref()) {
+            Ok(tree) => tree,
+            Err(e) => {
+                tracing::warn!(error = %e, "error parsing template");
+                // the previous analysis has spans into the previous text, so drop it rather than
+                // answer requests with positions that no longer match
+                self.tree = None;
+                self.template = Template::default();
+                self.jump_destinations = Vec::new();
+                self.jump_sources = Vec::new();
+                self.diagnostics = Vec::new();
+                self.workspace_symbols = Vec::new();
+                return;
+            }
+        };
+        self.template = Template::from_tree(&tree, contents, language);
+        self.tree = Some(tree);
+        self.jump_destinations = Destinations::new(&self.template).definitions();
+        tracing::debug!(destinations = ?self.jump_destinations, "extracted goto definition targets");
+
+        self.jump_sources = references(&self.template, contents);
+        tracing::debug!(count = self.jump_sources.len(), "extracted jump sources");
+
+        self.diagnostics = diagnostics::unresolved_references(
+            &self.rope,
+            &self.jump_sources,
+            &self.jump_destinations,
+        );
+        self.diagnostics.extend(diagnostics::invalid_attributes(
+            &self.rope,
+            &self.jump_sources,
+            &self.template,
+        ));
+        self.diagnostics.extend(diagnostics::invalid_map_keys(
+            &self.rope,
+            &self.jump_sources,
+            &self.template,
+        ));
+        self.diagnostics
+            .extend(validation::schema_violations(&self.rope, &self.template));
+
+        self.workspace_symbols = if workspace::is_template(&self.template) {
+            workspace::template_symbols(&self.template, |span| lsp_range(&self.rope, span))
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Convert an LSP position into a position in the analysed text, with the column counted in
+    /// bytes like the spans of the syntax tree
+    pub(crate) fn text_position(&self, position: Position) -> template::Position {
+        let byte = self.rope.char_to_byte(self.char_index(position));
+        self.point(byte).into()
+    }
+
+    /// Convert an LSP position into a byte offset into the analysed text
+    pub(crate) fn byte_offset(&self, position: Position) -> usize {
+        self.rope.char_to_byte(self.char_index(position))
+    }
+
+    /// Convert a position in the analysed text into an LSP position, whose column counts UTF-16
+    /// code units
+    pub(crate) fn lsp_position(&self, position: template::Position) -> Position {
+        lsp_position(&self.rope, position)
+    }
+
+    /// Convert a span of the analysed text into an LSP range, whose columns count UTF-16 code
+    /// units
+    pub(crate) fn lsp_range(&self, span: Span) -> Range {
+        lsp_range(&self.rope, span)
+    }
+
+    /// The innermost named syntax node at the position of the analysed text
+    pub(crate) fn node_at(&self, position: template::Position) -> Option<Node<'_>> {
+        let point = Point {
+            row: position.line,
+            column: position.col,
+        };
+        self.tree
+            .as_ref()?
+            .root_node()
+            .named_descendant_for_point_range(point, point)
+    }
+}
+
+/// Convert a position in the text of the rope, with the column counted in bytes, into an LSP
+/// position, clamping positions past the end of a line or the text
+pub(crate) fn lsp_position(rope: &Rope, position: template::Position) -> Position {
+    let line = position.line.min(rope.len_lines() - 1);
+    let text = rope.line(line);
+    let mut line_end = text.len_bytes();
+    while line_end > 0 && matches!(text.byte(line_end - 1), b'\n' | b'\r') {
+        line_end -= 1;
+    }
+    let column = if line == position.line {
+        position.col.min(line_end)
+    } else {
+        line_end
+    };
+    let character = text.char_to_utf16_cu(text.byte_to_char(column));
+    Position::new(line as u32, character as u32)
+}
+
+/// Convert a span of the text of the rope into an LSP range
+pub(crate) fn lsp_range(rope: &Rope, span: Span) -> Range {
+    Range {
+        start: lsp_position(rope, span.start),
+        end: lsp_position(rope, span.end),
+    }
+}
+
+/// Open a document for a test, named `name` under `file:///templates/`
+#[cfg(test)]
+pub(crate) fn test_document(name: &str, language_id: &str, text: &str) -> Document {
+    Document::new(TextDocumentItem {
+        uri: Url::parse(&format!("file:///templates/{name}")).unwrap(),
+        language_id: language_id.to_string(),
+        version: 1,
+        text: text.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::template::parse_tree;
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position::new(start.0, start.1),
+                end: Position::new(end.0, end.1),
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    /// The incrementally reparsed tree must match parsing the final text from scratch
+    fn assert_tree_matches_full_parse(doc: &Document) {
+        let expected = parse_tree(&doc.text, doc.language).unwrap();
+        assert_eq!(
+            doc.tree.as_ref().unwrap().root_node().to_sexp(),
+            expected.root_node().to_sexp()
+        );
+    }
+
+    #[test]
+    fn language_id_selects_grammar() {
+        let contents = include_str!("../testdata/sections.json");
+        let doc = test_document("stack.template", "json", contents);
+
+        assert_eq!(doc.language, TemplateLanguage::Json);
+        assert_eq!(doc.jump_destinations.len(), 6);
+        assert!(!doc.jump_sources.is_empty());
+    }
+
+    #[test]
+    fn full_change_recomputes_analysis() {
+        let mut doc = test_document(
+            "stack.yml",
+            "yaml",
+            include_str!("../testdata/two_resources.yml"),
+        );
+        assert!(doc.diagnostics.is_empty());
+
+        let text = doc.text.replace("!Ref Topic", "!Ref Topci");
+        doc.apply_changes(
+            [TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text,
+            }],
+            2,
+        );
+        doc.ensure_analysed();
+
+        assert_eq!(doc.version, 2);
+        assert_eq!(doc.diagnostics.len(), 1);
+        assert_tree_matches_full_parse(&doc);
+    }
+
+    #[test]
+    fn incremental_edits() {
+        let mut doc = test_document(
+            "stack.yml",
+            "yaml",
+            include_str!("../testdata/two_resources.yml"),
+        );
+
+        // rename the topic, which breaks the reference on line 8
+        doc.apply_changes([edit((1, 2), (1, 7), "MyTopic")], 2);
+        doc.ensure_analysed();
+        assert!(doc.text.starts_with("Resources:\n  MyTopic:\n"));
+        assert_eq!(doc.diagnostics.len(), 1);
+        assert_tree_matches_full_parse(&doc);
+
+        // fix the reference and add a new resource in a single notification
+        doc.apply_changes(
+            [
+                edit((8, 18), (8, 23), "MyTopic"),
+                edit((9, 0), (9, 0), "  Queue:\n    Type: AWS::SQS::Queue\n"),
+            ],
+            3,
+        );
+        doc.ensure_analysed();
+        assert!(doc.diagnostics.is_empty());
+        assert!(doc.jump_destinations.iter().any(|d| d.name == "Queue"));
+        assert_tree_matches_full_parse(&doc);
+    }
+
+    #[test]
+    fn edits_are_applied_in_utf16_code_units() {
+        let mut doc = test_document(
+            "stack.yml",
+            "yaml",
+            "Description: \u{1F600} caf\u{e9}\nResources:\n  Topic:\n    Type: AWS::SNS::Topic\n",
+        );
+
+        // the emoji is two UTF-16 code units, so "café" starts at character 16
+        doc.apply_changes([edit((0, 16), (0, 20), "bar")], 2);
+        doc.ensure_analysed();
+
+        assert!(doc.text.starts_with("Description: \u{1F600} bar\n"));
+        assert_tree_matches_full_parse(&doc);
+    }
+
+    #[test]
+    fn edits_past_the_end_of_a_line_keep_the_newline() {
+        let mut doc = test_document(
+            "stack.yml",
+            "yaml",
+            "Description: a\r\nResources:\n  Topic:\n    Type: AWS::SNS::Topic\n",
+        );
+
+        doc.apply_changes([edit((0, 14), (0, 40), "bc")], 2);
+        doc.apply_changes([edit((1, 30), (1, 50), " # topics")], 3);
+        doc.ensure_analysed();
+
+        assert_eq!(
+            doc.text,
+            "Description: abc\r\nResources: # topics\n  Topic:\n    Type: AWS::SNS::Topic\n"
+        );
+        assert_tree_matches_full_parse(&doc);
+    }
+
+    #[test]
+    fn positions_convert_between_bytes_and_utf16() {
+        let doc = test_document(
+            "stack.yml",
+            "yaml",
+            "Description: \u{1F600} caf\u{e9} x\nResources: {}\n",
+        );
+        let byte_position = |line, col| template::Position { line, col };
+        // the emoji is four bytes but two UTF-16 code units, and `é` is two bytes but one unit
+        let x = byte_position(0, 24);
+        assert_eq!(lsp_position(&doc.rope, x), Position::new(0, 21));
+        assert_eq!(doc.text_position(Position::new(0, 21)), x);
+        assert_eq!(doc.byte_offset(Position::new(0, 21)), 24);
+        assert_eq!(
+            doc.lsp_range(Span {
+                start: byte_position(0, 18),
+                end: byte_position(0, 23),
+            }),
+            Range::new(Position::new(0, 16), Position::new(0, 20))
+        );
+        // past the end of a line or the document
+        assert_eq!(
+            lsp_position(&doc.rope, byte_position(1, 40)),
+            Position::new(1, 13)
+        );
+        assert_eq!(
+            lsp_position(&doc.rope, byte_position(7, 0)),
+            Position::new(2, 0)
+        );
+    }
+
+    #[test]
+    fn analysis_is_deferred_until_requested() {
+        let mut doc = test_document(
+            "stack.yml",
+            "yaml",
+            include_str!("../testdata/two_resources.yml"),
+        );
+        doc.apply_changes([edit((1, 2), (1, 7), "MyTopic")], 2);
+
+        assert_eq!(doc.generation(), 1);
+        assert!(doc.jump_destinations.iter().any(|d| d.name == "Topic"));
+        doc.ensure_analysed();
+        assert!(doc.jump_destinations.iter().any(|d| d.name == "MyTopic"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Highlighting every occurrence of the symbol under the cursor for
+//! `textDocument/documentHighlight`
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Position};
+
+use crate::{
+    document::Document,
+    symbols::{self, Occurrence},
+};
+
+/// The declaration of the symbol under the cursor as a write, and every usage as a read
+///
+/// Names that are not declared in the template, such as pseudo parameters, highlight every usage
+/// of the same name instead.
+pub(crate) fn document_highlights(
+    document: &Document,
+    position: Position,
+) -> Option<Vec<DocumentHighlight>> {
+    let occurrences = symbols::occurrences(document);
+    let occurrence = symbols::occurrence_at(document, &occurrences, position)?;
+    let highlights = symbols::related(document, &occurrences, occurrence)
+        .into_iter()
+        .map(|occurrence| highlight(document, occurrence))
+        .collect();
+    Some(highlights)
+}
+
+fn highlight(document: &Document, occurrence: &Occurrence) -> DocumentHighlight {
+    DocumentHighlight {
+        range: document.lsp_range(occurrence.span),
+        kind: Some(if occurrence.is_declaration {
+            DocumentHighlightKind::WRITE
+        } else {
+            DocumentHighlightKind::READ
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Start line, start column and kind of each highlight, in document order
+    fn highlights(doc: &Document, position: Position) -> Vec<(u32, u32, DocumentHighlightKind)> {
+        let mut highlights: Vec<_> = document_highlights(doc, position)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|h| (h.range.start.line, h.range.start.character, h.kind.unwrap()))
+            .collect();
+        highlights.sort_by_key(|&(line, col, _)| (line, col));
+        highlights
+    }
+
+    #[test]
+    fn resource_declaration_and_usages() {
+        let doc = test_document("stack.yml", "yaml", include_str!("../testdata/rename.yml"));
+        let read = DocumentHighlightKind::READ;
+        let expected = vec![
+            (8, 2, DocumentHighlightKind::WRITE),
+            // DependsOn: Topic
+            (14, 15, read),
+            // !GetAtt Topic.TopicName
+            (16, 38, read),
+            // DependsOn list
+            (22, 8, read),
+            // ${Topic.TopicName}
+            (26, 20, read),
+            // !Ref "Topic"
+            (31, 17, read),
+            // Fn::GetAtt: [Topic, TopicName]
+            (34, 19, read),
+        ];
+        // the same symbol is highlighted from its declaration and from any usage
+        for position in [
+            Position::new(8, 4),
+            Position::new(16, 42),
+            Position::new(22, 10),
+            Position::new(26, 23),
+            Position::new(31, 19),
+        ] {
+            assert_eq!(highlights(&doc, position), expected, "{position:?}");
+        }
+    }
+
+    #[test]
+    fn condition_usages() {
+        let doc = test_document("stack.yml", "yaml", include_str!("../testdata/rename.yml"));
+        let lines: Vec<_> = highlights(&doc, Position::new(16, 24))
+            .into_iter()
+            .map(|(line, _, kind)| (line, kind))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (5, DocumentHighlightKind::WRITE),
+                (13, DocumentHighlightKind::READ),
+                (16, DocumentHighlightKind::READ),
+                (30, DocumentHighlightKind::READ),
+            ]
+        );
+    }
+
+    #[test]
+    fn undeclared_names() {
+        let doc = test_document(
+            "stack.yml",
+            "yaml",
+            "Resources:\n  Topic:\n    Type: AWS::SNS::Topic\n    Properties:\n      \
+             TopicName: !Sub \"${AWS::Region}-${Missing}\"\n      \
+             DisplayName: !Ref AWS::Region\n",
+        );
+        let read = DocumentHighlightKind::READ;
+        assert_eq!(
+            highlights(&doc, Position::new(5, 26)),
+            [(4, 25, read), (5, 24, read)]
+        );
+        assert_eq!(highlights(&doc, Position::new(4, 42)), [(4, 40, read)]);
+        // whitespace and the literal text of a `Sub` string are not symbols
+        assert_eq!(document_highlights(&doc, Position::new(4, 2)), None);
+        assert_eq!(document_highlights(&doc, Position::new(4, 38)), None);
+    }
+}
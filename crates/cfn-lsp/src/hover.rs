@@ -0,0 +1,428 @@
+//! Hover information for template symbols and resource types
+use cfn_lsp_schema::PathSegment;
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position};
+
+use crate::{
+    TemplateLanguage,
+    destinations::JumpDestinationType,
+    document::Document,
+    references::ReferenceType,
+    symbols,
+    template::{Condition, Parameter, Resource, Span, Spanned, Value},
+};
+
+/// Hover for the symbol, property key or resource type under the cursor
+///
+/// Logical IDs, parameter names and condition names show a summary of their declaration wherever
+/// they appear. Property keys show their documentation from the resource provider schema, and
+/// anything else in a `Type` value shows the description of the resource type.
+pub(crate) fn hover(document: &Document, position: Position) -> Option<Hover> {
+    if let Some(hover) = symbol(document, position) {
+        return Some(hover);
+    }
+    if let Some(hover) = property(document, position) {
+        return Some(hover);
+    }
+
+    let resource_type = document
+        .template
+        .resource_type_at(document.text_position(position))?;
+    let resource_info = match cfn_lsp_schema::extract_resource_from_bundle(resource_type) {
+        Ok(info) => info,
+        Err(e) => {
+            tracing::warn!(%e, "error extracting resource info");
+            return None;
+        }
+    };
+    Some(Hover {
+        contents: markdown(resource_info.description.unwrap_or_default()),
+        range: None,
+    })
+}
+
+fn symbol(document: &Document, position: Position) -> Option<Hover> {
+    let occurrences = symbols::occurrences(document);
+    let occurrence = symbols::occurrence_at(document, &occurrences, position)?;
+    let declaration = symbols::declaration(document, occurrence)?;
+
+    let template = &document.template;
+    let value = match declaration.r#type {
+        JumpDestinationType::Resource => {
+            let resource = template
+                .resources
+                .iter()
+                .flat_map(|section| &section.entries)
+                .find(|resource| resource.name.value == declaration.name)?;
+            resource_summary(resource, is_ref(document, position))
+        }
+        JumpDestinationType::Parameter => {
+            let parameter = template
+                .parameters
+                .iter()
+                .flat_map(|section| &section.entries)
+                .find(|parameter| parameter.name.value == declaration.name)?;
+            parameter_summary(parameter)
+        }
+        JumpDestinationType::Condition => {
+            let condition = template
+                .conditions
+                .iter()
+                .flat_map(|section| &section.entries)
+                .find(|condition| condition.name.value == declaration.name)?;
+            condition_summary(document, condition)
+        }
+        JumpDestinationType::Mapping | JumpDestinationType::Output => return None,
+    };
+    Some(Hover {
+        contents: markdown(value),
+        range: Some(document.lsp_range(occurrence.span)),
+    })
+}
+
+fn property(document: &Document, position: Position) -> Option<Hover> {
+    let position = document.text_position(position);
+    let resource = document
+        .template
+        .resources
+        .iter()
+        .flat_map(|section| &section.entries)
+        .find(|resource| {
+            resource
+                .properties
+                .as_ref()
+                .is_some_and(|properties| properties.span.contains(position))
+        })?;
+    let mut path = Vec::new();
+    let key = key_at(resource.properties.as_ref()?, position, &mut path)?;
+
+    let resource_type = resource.resource_type.as_ref()?;
+    let schema = match cfn_lsp_schema::resource_schema(&resource_type.value) {
+        Ok(schema) => schema,
+        Err(e) => {
+            tracing::debug!(error = %e, resource_type = %resource_type.value, "no schema for resource type");
+            return None;
+        }
+    };
+    let (_, parent_path) = path.split_last()?;
+    let parent = schema.lookup(parent_path)?;
+    // the declared schema names the definition it refers to, which describes the type better
+    let declared = schema
+        .properties(parent)
+        .into_iter()
+        .find(|(name, _)| *name == key.value)
+        .map(|(_, declared)| declared);
+    let property = schema.lookup(&path)?;
+
+    let mut value = format!(
+        "**{}** `{}`",
+        key.value,
+        schema.type_name(declared.unwrap_or(property))
+    );
+    let access = schema.access(&path);
+    let mut notes = Vec::new();
+    if schema.resolve(parent).required.contains(&key.value) {
+        notes.push("Required");
+    }
+    if access.create_only {
+        notes.push("Create only, changing it replaces the resource");
+    }
+    if access.write_only {
+        notes.push("Write only, it is not returned when the resource is read");
+    }
+    if access.read_only {
+        notes.push("Read only, it is set by the service");
+    }
+    if !notes.is_empty() {
+        value.push_str("\n\n");
+        value.push_str(
+            &notes
+                .iter()
+                .map(|note| format!("- {note}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+    }
+    if let Some(description) = declared
+        .and_then(|declared| declared.description.as_ref())
+        .or(property.description.as_ref())
+    {
+        value.push_str(&format!("\n\n{description}"));
+    }
+    if let Some(values) = &property.enum_values {
+        let values: Vec<_> = values
+            .iter()
+            .map(|value| match value {
+                serde_json::Value::String(s) => format!("`{s}`"),
+                other => format!("`{other}`"),
+            })
+            .collect();
+        value.push_str(&format!("\n\nAllowed values: {}", values.join(", ")));
+    }
+    Some(Hover {
+        contents: markdown(value),
+        range: Some(document.lsp_range(key.span)),
+    })
+}
+
+/// The property key under the cursor, with the path to it from the resource properties
+fn key_at<'v>(
+    value: &'v Spanned<Value>,
+    position: crate::template::Position,
+    path: &mut Vec<PathSegment<'v>>,
+) -> Option<&'v Spanned<String>> {
+    // keys nested in intrinsic functions are not properties
+    if value.value.as_intrinsic().is_some() {
+        return None;
+    }
+    match &value.value {
+        Value::Mapping(pairs) => {
+            let (key, value) = pairs.iter().find(|(key, value)| {
+                key.span.contains(position) || value.span.contains(position)
+            })?;
+            path.push(PathSegment::Key(&key.value));
+            if key.span.contains(position) {
+                return Some(key);
+            }
+            key_at(value, position, path)
+        }
+        Value::Sequence(items) => {
+            let item = items.iter().find(|item| item.span.contains(position))?;
+            path.push(PathSegment::Item);
+            key_at(item, position, path)
+        }
+        _ => None,
+    }
+}
+
+/// Whether the cursor is on the target of a `Ref`, or a `${}` placeholder which behaves the same
+fn is_ref(document: &Document, position: Position) -> bool {
+    let position = document.text_position(position);
+    document
+        .jump_sources
+        .iter()
+        .any(|reference| match &reference.typ {
+            ReferenceType::Ref(_) => reference.contains(position),
+            ReferenceType::Sub(s) => {
+                s.attribute.is_none() && s.local.is_none() && reference.contains(position)
+            }
+            _ => false,
+        })
+}
+
+fn resource_summary(resource: &Resource, is_ref: bool) -> String {
+    let Some(resource_type) = &resource.resource_type else {
+        return format!("**{}** resource", resource.name.value);
+    };
+    let mut value = format!("**{}** `{}`", resource.name.value, resource_type.value);
+    let info = match cfn_lsp_schema::extract_resource_from_bundle(&resource_type.value) {
+        Ok(info) => info,
+        Err(e) => {
+            tracing::debug!(error = %e, resource_type = %resource_type.value, "no schema for resource type");
+            return value;
+        }
+    };
+    if is_ref && !info.primary_identifier.is_empty() {
+        // compound identifiers are joined with `|`
+        value.push_str(&format!(
+            "\n\nPrimary identifier: `{}`",
+            info.primary_identifier
+        ));
+    }
+    if let Some(description) = info.description {
+        value.push_str(&format!("\n\n{description}"));
+    }
+    value
+}
+
+fn parameter_summary(parameter: &Parameter) -> String {
+    let mut value = format!("**{}** parameter", parameter.name.value);
+    if let Some(parameter_type) = &parameter.parameter_type {
+        value.push_str(&format!("\n\nType: `{}`", parameter_type.value));
+    }
+    if let Some(default) = &parameter.default {
+        value.push_str(&format!("\n\nDefault: {}", display(&default.value)));
+    }
+    if let Some(allowed_values) = &parameter.allowed_values {
+        value.push_str(&format!(
+            "\n\nAllowed values: {}",
+            display(&allowed_values.value)
+        ));
+    }
+    if let Some(description) = &parameter.description {
+        value.push_str(&format!("\n\n{}", description.value));
+    }
+    value
+}
+
+fn condition_summary(document: &Document, condition: &Condition) -> String {
+    let language = match document.language {
+        TemplateLanguage::Yaml => "yaml",
+        TemplateLanguage::Json => "json",
+    };
+    format!(
+        "**{}** condition\n\n```{language}\n{}\n```",
+        condition.name.value,
+        source(&document.text, condition.definition.span)
+    )
+}
+
+/// Inline code for scalar values, and for each item of a list of scalars
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => "`null`".to_string(),
+        Value::Bool(b) => format!("`{b}`"),
+        Value::Number(s) | Value::String(s) => format!("`{s}`"),
+        Value::Sequence(items) => items
+            .iter()
+            .map(|item| display(&item.value))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Mapping(_) => "a mapping".to_string(),
+    }
+}
+
+/// Source text of the span, with the lines after the first dedented by the indentation of the
+/// first line so that nesting is kept relative to the declaration
+fn source(text: &str, span: Span) -> String {
+    let lines: Vec<_> = text
+        .lines()
+        .skip(span.start.line)
+        .take(span.end.line + 1 - span.start.line)
+        .collect();
+    let Some((first, rest)) = lines.split_first() else {
+        return String::new();
+    };
+    let indent = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .chain([first.len() - first.trim_start().len()])
+        .min()
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    if rest.is_empty() {
+        out.push_str(first.get(span.start.col..span.end.col).unwrap_or_default());
+        return out;
+    }
+    out.push_str(first.get(span.start.col..).unwrap_or_default());
+    for (i, line) in rest.iter().enumerate() {
+        let line = if i + 1 == rest.len() {
+            line.get(..span.end.col).unwrap_or(line)
+        } else {
+            line
+        };
+        out.push('\n');
+        out.push_str(line.get(indent..).unwrap_or(line.trim_start()));
+    }
+    out.trim_end().to_string()
+}
+
+fn markdown(value: String) -> HoverContents {
+    HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Markdown of the hover at the first occurrence of `needle` on the line
+    fn hover_text(document: &Document, line: u32, needle: &str) -> Option<String> {
+        let text = document.text.lines().nth(line as usize).unwrap();
+        let character = text.find(needle).unwrap() as u32;
+        let hover = hover(document, Position::new(line, character))?;
+        let HoverContents::Markup(content) = hover.contents else {
+            panic!("expected markdown");
+        };
+        Some(content.value)
+    }
+
+    #[test]
+    fn symbol_summaries() {
+        let doc = test_document("stack", "yaml", include_str!("../testdata/hover.yml"));
+        let hovers: Vec<_> = [
+            (17, "Environment"),
+            (20, "Bucket"),
+            (25, "Bucket"),
+            (23, "Bucket"),
+            (15, "IsProduction"),
+            (27, "Topic"),
+            (7, "IsProduction"),
+        ]
+        .into_iter()
+        .map(|(line, needle)| hover_text(&doc, line, needle))
+        .collect();
+        insta::assert_yaml_snapshot!(hovers);
+    }
+
+    #[test]
+    fn json_condition_expression() {
+        let doc = test_document("stack", "json", include_str!("../testdata/conditions.json"));
+        let line = doc
+            .text
+            .lines()
+            .position(|line| line.contains("\"Condition\":"))
+            .unwrap();
+        let text = hover_text(&doc, line as u32, "IsProd").unwrap();
+        assert!(text.starts_with("**IsProd"), "{text}");
+        assert!(text.contains("```json\n{"), "{text}");
+    }
+
+    #[test]
+    fn resource_type_description() {
+        let doc = test_document("stack", "yaml", include_str!("../testdata/hover.yml"));
+        let text = hover_text(&doc, 12, "AWS::S3::Bucket").unwrap();
+        assert!(!text.starts_with("**"), "{text}");
+    }
+
+    #[test]
+    fn property_documentation() {
+        let doc = test_document(
+            "stack",
+            "yaml",
+            include_str!("../testdata/hover_properties.yml"),
+        );
+        let hovers: Vec<_> = [
+            (4, "RoleName"),
+            (5, "AssumeRolePolicyDocument"),
+            (10, "PolicyName"),
+            (17, "StorageClass"),
+        ]
+        .into_iter()
+        .map(|(line, needle)| hover_text(&doc, line, needle))
+        .collect();
+        insta::assert_yaml_snapshot!(hovers);
+
+        // keys inside a policy document are not described by the schema
+        assert_eq!(hover_text(&doc, 6, "Version"), None);
+    }
+
+    #[test]
+    fn json_property_documentation() {
+        let doc = test_document(
+            "stack",
+            "json",
+            r#"{"Resources": {"Queue": {"Type": "AWS::SQS::Queue", "Properties": {"QueueName": "jobs"}}}}"#,
+        );
+        let text = hover_text(&doc, 0, "QueueName").unwrap();
+        assert!(text.starts_with("**QueueName** `string`"), "{text}");
+        assert!(text.contains("- Create only"), "{text}");
+    }
+
+    #[test]
+    fn dedented_source() {
+        let text = "Conditions:\n  IsProduction:\n    Fn::Equals:\n      - !Ref Environment\n      - production\n";
+        let span = Span {
+            start: crate::template::Position { line: 2, col: 4 },
+            end: crate::template::Position { line: 4, col: 18 },
+        };
+        assert_eq!(
+            source(text, span),
+            "Fn::Equals:\n  - !Ref Environment\n  - production"
+        );
+    }
+}
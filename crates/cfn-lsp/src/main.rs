@@ -1,139 +1,127 @@
 //! Entry crate to the LSP itself
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Context;
 use tokio::sync::Mutex;
 use tower_lsp::{
     Client, LanguageServer, LspService, Server,
     lsp_types::{
-        CompletionOptions, CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
-        DidOpenTextDocumentParams, DidSaveTextDocumentParams, Documentation, GotoDefinitionParams,
-        GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
-        InitializeParams, InitializeResult, Location, MarkupContent, MarkupKind, OneOf, Position,
-        ReferenceParams, ServerCapabilities, ServerInfo, TextDocumentItem,
-        TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+        CodeActionParams, CodeActionProviderCapability, CodeActionResponse, CompletionOptions,
+        CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
+        DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+        DocumentHighlight, DocumentHighlightParams, DocumentSymbolParams, DocumentSymbolResponse,
+        FileChangeType, FileSystemWatcher, GlobPattern, GotoDefinitionParams,
+        GotoDefinitionResponse, Hover, HoverParams, HoverProviderCapability, InitializeParams,
+        InitializeResult, InitializedParams, Location, OneOf, PrepareRenameResponse,
+        ReferenceParams, Registration, RenameOptions, RenameParams, SemanticTokens,
+        SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensParams,
+        SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult,
+        SemanticTokensServerCapabilities, ServerCapabilities, ServerInfo, SymbolInformation,
+        TextDocumentContentChangeEvent, TextDocumentPositionParams, TextDocumentSyncCapability,
+        TextDocumentSyncKind, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+        WorkspaceEdit, WorkspaceSymbolParams,
     },
 };
 use tracing::Level;
 
-use crate::{
-    destinations::{Destinations, JumpDestination},
-    queries::{Extractor, Reference},
-};
+use crate::{document::Document, references::ReferenceType, workspace::WorkspaceIndex};
 
+mod code_actions;
+mod completion;
 mod destinations;
-mod queries;
+mod diagnostics;
+mod document;
+mod highlight;
+mod hover;
+mod outline;
+mod references;
+mod rename;
+mod semantic_tokens;
+mod symbols;
+mod template;
+mod validation;
+mod workspace;
 
 // lsp
 
 struct ServerState {
-    _client: Client,
+    client: Client,
     inner: Arc<Mutex<ServerStateInner>>,
 }
 
-impl ServerState {
-    async fn set_current_document_from_url(&self, url: Url) {
-        let Ok(path) = url.to_file_path() else {
-            tracing::warn!(?url, "cannot be converted to path");
-            return;
-        };
-        tracing::debug!(uri = %path.display(), "opened file");
-        let Ok(contents) = std::fs::read_to_string(&path) else {
-            tracing::warn!(path = %path.display(), "could not read file");
-            return;
-        };
-        let mut inner = self.inner.lock().await;
-        inner.current_document = Some(TextDocumentItem {
-            uri: url,
-            language_id: "".to_string(),
-            version: 0,
-            text: contents.clone(),
-        });
-
-        // also recompute the jump destinations
-        let mut destinations = Destinations::new(&contents);
-        match destinations.definitions() {
-            Ok(destinations) => {
-                tracing::debug!(?destinations, "extracted goto definition targets");
-                inner.jump_destinations = destinations;
-            }
-            Err(e) => {
-                tracing::warn!(error = %e, "error computing jump destinations");
-            }
-        }
+/// How long to wait after the last edit before re-publishing diagnostics
+const DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_millis(200);
 
-        match Extractor::new(&contents) {
-            Ok(extractor) => match extractor.extract_all(&contents) {
-                Ok(all_references) => {
-                    tracing::debug!(count = all_references.len(), "extracted jump sources");
-                    inner.jump_sources = all_references;
-                }
-                Err(e) => {
-                    tracing::warn!(error = %e, "error extracting jump sources");
-                }
-            },
-            Err(e) => {
-                tracing::warn!(error = %e, "error computing jump sources");
-            }
-        }
-    }
-}
-
-// free floating function to make testing easier
-fn word_under_cursor(content: &str, cursor: Position) -> anyhow::Result<Option<String>> {
-    let line_number = usize::try_from(cursor.line).unwrap();
-    let character_number = usize::try_from(cursor.character).unwrap();
-
-    let mut lines = content.split('\n');
-    let current_line = lines
-        .nth(line_number)
-        .ok_or(anyhow::anyhow!("Line out of bounds"))?;
-
-    // check if the character at the current position is a space or not. If so return None
-    let current_char = current_line
-        .chars()
-        .nth(character_number)
-        .ok_or(anyhow::anyhow!("character out of range"))?;
-    if current_char.is_whitespace() {
-        return Ok(None);
+impl ServerState {
+    async fn publish_diagnostics(&self, uri: &Url) {
+        publish_diagnostics(&self.client, &self.inner, uri, None).await;
     }
 
-    // find index of end of word
-    let chars: Vec<_> = current_line.chars().collect();
-
-    let mut start_index = character_number;
-    while start_index > 0 {
-        if chars[start_index].is_whitespace() {
-            start_index += 1;
-            break;
-        }
-        start_index -= 1;
+    /// Publish diagnostics once the document has stopped changing for a short while
+    fn schedule_diagnostics(&self, uri: Url, generation: u64) {
+        let client = self.client.clone();
+        let inner = Arc::clone(&self.inner);
+        tokio::spawn(async move {
+            tokio::time::sleep(DIAGNOSTICS_DEBOUNCE).await;
+            publish_diagnostics(&client, &inner, &uri, Some(generation)).await;
+        });
     }
+}
 
-    let mut end_index = character_number;
-    while end_index < current_line.len() {
-        if chars[end_index].is_whitespace() {
-            break;
+/// Analyse the document if required and publish its diagnostics
+///
+/// If `generation` is given, nothing is published unless the document is still at that
+/// generation, i.e. no further edits have arrived in the meantime.
+async fn publish_diagnostics(
+    client: &Client,
+    inner: &Mutex<ServerStateInner>,
+    uri: &Url,
+    generation: Option<u64>,
+) {
+    let (diagnostics, version) = {
+        let mut inner = inner.lock().await;
+        let Some(document) = inner.documents.get_mut(uri) else {
+            return;
+        };
+        if generation.is_some_and(|generation| generation != document.generation()) {
+            tracing::trace!(%uri, "document changed again, skipping diagnostics");
+            return;
         }
-        end_index += 1;
-    }
-
-    Ok(Some(current_line[start_index..end_index].to_string()))
+        document.ensure_analysed();
+        (document.diagnostics.clone(), document.version)
+    };
+    tracing::debug!(%uri, count = diagnostics.len(), "publishing diagnostics");
+    client
+        .publish_diagnostics(uri.clone(), diagnostics, Some(version))
+        .await;
 }
 
 struct ServerStateInner {
-    current_document: Option<TextDocumentItem>,
-    jump_destinations: Vec<JumpDestination>,
-    jump_sources: Vec<Reference>,
+    /// Open documents keyed by their URI
+    documents: HashMap<Url, Document>,
+    /// Workspace folders sent by the client, which are searched for templates
+    roots: Vec<PathBuf>,
+    /// Whether the client lets us register file watchers to keep the index up to date
+    watch_files: bool,
+    /// Symbols of every template on disk under the roots
+    index: WorkspaceIndex,
 }
 
 impl ServerStateInner {
-    async fn word_under_cursor(&self, cursor: Position) -> anyhow::Result<Option<String>> {
-        let Some(doc) = &self.current_document else {
-            return Ok(None);
+    /// Look up an open document, bringing its analysis up to date with any pending edits
+    fn document(&mut self, uri: &Url) -> Option<&Document> {
+        let Some(document) = self.documents.get_mut(uri) else {
+            tracing::warn!(%uri, "document is not open");
+            return None;
         };
-
-        word_under_cursor(&doc.text, cursor)
+        document.ensure_analysed();
+        Some(document)
     }
 }
 
@@ -144,15 +132,62 @@ impl LanguageServer for ServerState {
         params: InitializeParams,
     ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
         tracing::debug!(?params, "initializing server");
+        {
+            let mut inner = self.inner.lock().await;
+            #[allow(deprecated)]
+            let folders = match (params.workspace_folders, params.root_uri) {
+                (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+                // clients without workspace folder support send a single root
+                (None, Some(root_uri)) => vec![root_uri],
+                (None, None) => Vec::new(),
+            };
+            inner.roots = folders
+                .iter()
+                .filter_map(|uri| uri.to_file_path().ok())
+                .collect();
+            inner.watch_files = params
+                .capabilities
+                .workspace
+                .and_then(|workspace| workspace.did_change_watched_files)
+                .and_then(|watched| watched.dynamic_registration)
+                .unwrap_or_default();
+        }
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
-                completion_provider: Some(CompletionOptions::default()),
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                completion_provider: Some(CompletionOptions {
+                    // `!GetAtt Resource.` completes the attribute
+                    trigger_characters: Some(vec![".".to_string()]),
+                    ..Default::default()
+                }),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..Default::default()
+                        },
+                    ),
+                ),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -162,29 +197,129 @@ impl LanguageServer for ServerState {
         })
     }
 
+    async fn initialized(&self, _: InitializedParams) {
+        let (roots, watch_files) = {
+            let inner = self.inner.lock().await;
+            (inner.roots.clone(), inner.watch_files)
+        };
+        if watch_files {
+            let options = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(workspace::TEMPLATE_GLOB.to_string()),
+                    kind: None,
+                }],
+            };
+            let registration = Registration {
+                id: "watch-templates".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(options).ok(),
+            };
+            if let Err(e) = self.client.register_capability(vec![registration]).await {
+                tracing::warn!(error = %e, "could not register file watcher");
+            }
+        }
+
+        // walking the workspace can take a while, so do not hold up the first requests
+        let inner = Arc::clone(&self.inner);
+        tokio::spawn(async move {
+            match tokio::task::spawn_blocking(move || workspace::index_roots(&roots)).await {
+                // files changed while walking have already been indexed from their newer text
+                Ok(index) => inner.lock().await.index.merge(index),
+                Err(e) => tracing::warn!(error = %e, "indexing the workspace failed"),
+            }
+        });
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        tracing::trace!(?params, "watched files changed");
+        let sequence = self.inner.lock().await.index.next_sequence();
+        // reading and parsing the files blocks, so keep it off the async runtime
+        let updates = tokio::task::spawn_blocking(move || {
+            let mut updates = Vec::new();
+            for change in params.changes {
+                let Ok(path) = change.uri.to_file_path() else {
+                    continue;
+                };
+                if !workspace::is_template_path(&path) {
+                    continue;
+                }
+                if change.typ == FileChangeType::DELETED {
+                    updates.push((change.uri, None));
+                } else if let Some(update) = workspace::index_file(&path) {
+                    updates.push(update);
+                }
+            }
+            updates
+        })
+        .await;
+        let updates = match updates {
+            Ok(updates) => updates,
+            Err(e) => {
+                tracing::warn!(error = %e, "indexing changed files failed");
+                return;
+            }
+        };
+        let mut inner = self.inner.lock().await;
+        for (uri, symbols) in updates {
+            inner.index.update(uri, symbols, sequence);
+        }
+    }
+
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         tracing::trace!(?params, "document opened");
         let uri = params.text_document.uri.clone();
-        self.set_current_document_from_url(uri).await;
+        {
+            let mut inner = self.inner.lock().await;
+            inner
+                .documents
+                .insert(uri.clone(), Document::new(params.text_document));
+        }
+        self.publish_diagnostics(&uri).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         tracing::trace!(?params, "document changed");
-        let mut inner = self.inner.lock().await;
-        let Some(current_document) = inner.current_document.as_mut() else {
-            tracing::warn!("no current document");
-            return;
+        let uri = params.text_document.uri;
+        let generation = {
+            let mut inner = self.inner.lock().await;
+            let Some(document) = inner.documents.get_mut(&uri) else {
+                tracing::warn!(%uri, "change for a document that is not open");
+                return;
+            };
+            document.apply_changes(params.content_changes, params.text_document.version);
+            document.generation()
         };
-        current_document.text = params
-            .content_changes
-            .first()
-            .map_or(current_document.text.clone(), |change| change.text.clone());
+        self.schedule_diagnostics(uri, generation);
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
         tracing::trace!(?params, "document saved");
-        let url = params.text_document.uri.clone();
-        self.set_current_document_from_url(url).await;
+        let uri = params.text_document.uri;
+        // the in-memory document is kept up to date by `did_change`, so there is no need to
+        // re-read the file unless the client included the saved text
+        if let Some(text) = params.text {
+            let mut inner = self.inner.lock().await;
+            if let Some(document) = inner.documents.get_mut(&uri) {
+                let version = document.version;
+                document.apply_changes(
+                    [TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text,
+                    }],
+                    version,
+                );
+            }
+        }
+        self.publish_diagnostics(&uri).await;
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        tracing::trace!(?params, "document closed");
+        let uri = params.text_document.uri;
+        self.inner.lock().await.documents.remove(&uri);
+        // clear any diagnostics the editor is still showing for the document
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
     }
 
     async fn completion(
@@ -193,53 +328,18 @@ impl LanguageServer for ServerState {
     ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
         tracing::debug!(?params, "got completion request");
 
-        let uri = params.text_document_position.text_document.uri.clone();
-        let file_path = uri.to_file_path().map_err(|_| {
-            tracing::warn!(?uri, "cannot convert URI to file path");
-            tower_lsp::jsonrpc::Error::invalid_params("Invalid URI")
-        })?;
         // TODO: don't hold the mutex for the entire operation
-        let inner = self.inner.lock().await;
-        let Some(current_document) = inner.current_document.as_ref() else {
-            tracing::warn!("no current document");
+        let mut inner = self.inner.lock().await;
+        let Some(current_document) =
+            inner.document(&params.text_document_position.text_document.uri)
+        else {
             return Ok(None);
         };
-        let text = current_document.text.as_str();
-        let template_language = detect_template_language(&file_path, text);
-        tracing::debug!(?template_language, "detected template language");
         let pos = params.text_document_position.position;
-        let line = current_document
-            .text
-            .lines()
-            .nth(pos.line.try_into().unwrap())
-            .unwrap_or_default();
-
-        let should_complete = match template_language {
-            TemplateLanguage::Yaml => {
-                let prefix = "Type:";
-                line.trim_start().starts_with(prefix)
-            }
-            TemplateLanguage::Json => {
-                let prefix = "\"Type\":";
-                line.trim_start().starts_with(prefix)
-            }
-        };
-
-        if !should_complete {
-            tracing::debug!(?line, ?pos, "not completing");
+        let Some(completion_items) = completion::completions(current_document, pos) else {
+            tracing::debug!(?pos, "not completing");
             return Ok(None);
-        }
-
-        let completion_items: Vec<_> = cfn_lsp_schema::get_resource_types()
-            .iter()
-            .map(|resource| tower_lsp::lsp_types::CompletionItem {
-                label: resource.type_name.clone(),
-                kind: Some(tower_lsp::lsp_types::CompletionItemKind::CLASS),
-                // detail: Some(resource.type_name),
-                documentation: resource.description.clone().map(Documentation::String),
-                ..Default::default()
-            })
-            .collect();
+        };
 
         Ok(Some(CompletionResponse::Array(completion_items)))
     }
@@ -250,36 +350,59 @@ impl LanguageServer for ServerState {
     ) -> tower_lsp::jsonrpc::Result<Option<GotoDefinitionResponse>> {
         tracing::debug!(?params, "got goto definition request");
         let position = params.text_document_position_params.position;
-        let inner = self.inner.lock().await;
-        match inner.word_under_cursor(position).await {
-            Ok(Some(word)) => {
-                let mut candidates = Vec::new();
-                for destination in &inner.jump_destinations {
-                    if word == destination.name {
-                        candidates.push(destination);
-                    }
-                }
-
-                tracing::debug!(?candidates, "found match candidates");
-                if candidates.is_empty() {
-                    return Ok(None);
-                } else if candidates.len() > 1 {
-                    todo!("Unhandled case with more than one target: {:?}", candidates);
-                } else {
-                    let location = Location {
-                        uri: params.text_document_position_params.text_document.uri,
-                        range: candidates[0].span.to_range(),
-                    };
-                    let result = GotoDefinitionResponse::Scalar(location);
-                    tracing::debug!(?result, "returning jump response");
-                    return Ok(Some(result));
-                }
-            }
-            Ok(None) => todo!(),
-            Err(e) => tracing::warn!(error = %e, "No completions found"),
+        let mut inner = self.inner.lock().await;
+        let Some(document) =
+            inner.document(&params.text_document_position_params.text_document.uri)
+        else {
+            return Ok(None);
+        };
+        let uri = &params.text_document_position_params.text_document.uri;
+        // spans are in bytes, whereas the client counts UTF-16 code units
+        let text_position = document.text_position(position);
+        // variables declared in a `Fn::Sub` variable map jump to their key rather than a template
+        // symbol of the same name
+        if let Some(local) =
+            document
+                .jump_sources
+                .iter()
+                .find_map(|reference| match &reference.typ {
+                    ReferenceType::Sub(s) if reference.contains(text_position) => s.local,
+                    _ => None,
+                })
+        {
+            let location = Location {
+                uri: uri.clone(),
+                range: document.lsp_range(local),
+            };
+            return Ok(Some(GotoDefinitionResponse::Scalar(location)));
         }
-        tracing::debug!("no destinations found");
-        Ok(None)
+        // keys of `Fn::FindInMap` jump to the entry inside the mapping
+        if let Some(key) = symbols::mapping_key_at(document, text_position) {
+            let location = Location {
+                uri: uri.clone(),
+                range: document.lsp_range(key.span),
+            };
+            return Ok(Some(GotoDefinitionResponse::Scalar(location)));
+        }
+        let occurrences = symbols::occurrences(document);
+        let Some(occurrence) = symbols::occurrence_at(document, &occurrences, position) else {
+            tracing::debug!("no symbol under cursor");
+            return Ok(None);
+        };
+        let mut locations: Vec<_> = symbols::declarations(document, occurrence)
+            .map(|declaration| Location {
+                uri: uri.clone(),
+                range: document.lsp_range(declaration.span),
+            })
+            .collect();
+        tracing::debug!(?locations, "found declarations");
+        let response = match locations.len() {
+            0 => None,
+            1 => locations.pop().map(GotoDefinitionResponse::Scalar),
+            // a name declared more than once offers every declaration
+            _ => Some(GotoDefinitionResponse::Array(locations)),
+        };
+        Ok(response)
     }
 
     async fn references(
@@ -290,58 +413,26 @@ impl LanguageServer for ServerState {
         let position = params.text_document_position.position;
         let uri = params.text_document_position.text_document.uri.clone();
 
-        let inner = self.inner.lock().await;
-
-        // Get the word under cursor to find what symbol we're looking for
-        let Some(word) = inner.word_under_cursor(position).await.ok().flatten() else {
-            tracing::debug!("no word under cursor");
+        let mut inner = self.inner.lock().await;
+        let Some(document) = inner.document(&uri) else {
             return Ok(None);
         };
 
-        tracing::debug!(?word, "finding references for symbol");
-
-        // Find all references to this symbol in jump_sources
-        let mut locations = Vec::new();
-        for reference in &inner.jump_sources {
-            let target = match &reference.typ {
-                crate::queries::ReferenceType::Ref(r) => &r.target,
-                crate::queries::ReferenceType::Sub(s) => &s.target,
-                crate::queries::ReferenceType::GetAtt(g) => &g.target,
-                crate::queries::ReferenceType::FindInMap(f) => &f.target,
-                crate::queries::ReferenceType::If(i) => &i.target,
-                crate::queries::ReferenceType::DependsOn(d) => &d.target,
-            };
-
-            if target == &word {
-                let range = tower_lsp::lsp_types::Range {
-                    start: tower_lsp::lsp_types::Position {
-                        line: reference.start.line as u32,
-                        character: reference.start.col as u32,
-                    },
-                    end: tower_lsp::lsp_types::Position {
-                        line: reference.end.line as u32,
-                        character: reference.end.col as u32,
-                    },
-                };
-
-                locations.push(Location {
-                    uri: uri.clone(),
-                    range,
-                });
-            }
-        }
-
-        // If include_declaration is true, also include the definition location
-        if params.context.include_declaration {
-            for destination in &inner.jump_destinations {
-                if destination.name == word {
-                    locations.push(Location {
-                        uri: uri.clone(),
-                        range: destination.span.to_range(),
-                    });
-                }
-            }
-        }
+        let occurrences = symbols::occurrences(document);
+        let Some(occurrence) = symbols::occurrence_at(document, &occurrences, position) else {
+            tracing::debug!("no symbol under cursor");
+            return Ok(None);
+        };
+        tracing::debug!(name = %occurrence.name, "finding references for symbol");
+
+        let locations: Vec<_> = symbols::related(document, &occurrences, occurrence)
+            .into_iter()
+            .filter(|other| params.context.include_declaration || !other.is_declaration)
+            .map(|other| Location {
+                uri: uri.clone(),
+                range: document.lsp_range(other.span),
+            })
+            .collect();
 
         tracing::debug!(count = locations.len(), "found references");
 
@@ -352,42 +443,145 @@ impl LanguageServer for ServerState {
         }
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<DocumentHighlight>>> {
+        tracing::debug!(?params, "got document highlight request");
+        let position = params.text_document_position_params.position;
+        let mut inner = self.inner.lock().await;
+        let Some(document) =
+            inner.document(&params.text_document_position_params.text_document.uri)
+        else {
+            return Ok(None);
+        };
+        Ok(highlight::document_highlights(document, position))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<PrepareRenameResponse>> {
+        tracing::debug!(?params, "got prepare rename request");
+        let mut inner = self.inner.lock().await;
+        let Some(document) = inner.document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(
+            rename::prepare_rename(document, params.position).map(|(range, placeholder)| {
+                PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }
+            }),
+        )
+    }
+
+    async fn rename(
+        &self,
+        params: RenameParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<WorkspaceEdit>> {
+        tracing::debug!(?params, "got rename request");
+        let uri = params.text_document_position.text_document.uri;
+        let mut inner = self.inner.lock().await;
+        let Some(document) = inner.document(&uri) else {
+            return Ok(None);
+        };
+        let edits = rename::rename(
+            document,
+            params.text_document_position.position,
+            &params.new_name,
+        )
+        .map_err(|e| tower_lsp::jsonrpc::Error::invalid_params(e.to_string()))?;
+        Ok(edits.map(|edits| WorkspaceEdit {
+            changes: Some(HashMap::from([(uri, edits)])),
+            ..Default::default()
+        }))
+    }
+
+    async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CodeActionResponse>> {
+        tracing::debug!(?params, "got code action request");
+        let actions =
+            code_actions::quick_fixes(&params.text_document.uri, &params.context.diagnostics);
+        Ok(Some(actions))
+    }
+
     async fn hover(&self, params: HoverParams) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
         tracing::debug!(?params, "got hover request");
         let pos = params.text_document_position_params.position;
-        let inner = self.inner.lock().await;
-        let Some(current_document) = inner.current_document.as_ref() else {
-            tracing::warn!("no current document");
+        let mut inner = self.inner.lock().await;
+        let Some(document) =
+            inner.document(&params.text_document_position_params.text_document.uri)
+        else {
             return Ok(None);
         };
-        let text = current_document.text.as_str();
-        let mut lines = text.split('\n');
-        let Some(line) = lines
-            .nth(pos.line.try_into().unwrap())
-            .map(ToString::to_string)
-        else {
+        Ok(hover::hover(document, pos))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<DocumentSymbolResponse>> {
+        tracing::debug!(?params, "got document symbol request");
+        let mut inner = self.inner.lock().await;
+        let Some(document) = inner.document(&params.text_document.uri) else {
             return Ok(None);
         };
+        let symbols = outline::document_symbols(document);
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
 
-        let Some(resource_type) = extract_resource_type(&line, pos) else {
-            tracing::warn!(%line, ?pos, "no resource name found");
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SemanticTokensResult>> {
+        tracing::debug!(?params, "got semantic tokens request");
+        let mut inner = self.inner.lock().await;
+        let Some(document) = inner.document(&params.text_document.uri) else {
             return Ok(None);
         };
-        let resource_info =
-            cfn_lsp_schema::extract_resource_from_bundle(&resource_type).map_err(|e| {
-                tracing::warn!(%e, "error extracting resource info");
-                tower_lsp::jsonrpc::Error::internal_error()
-            })?;
-
-        // tracing::info!(?pos, ?line, ?resource_type,
-
-        Ok(Some(Hover {
-            contents: HoverContents::Markup(MarkupContent {
-                kind: MarkupKind::Markdown,
-                value: resource_info.description.unwrap_or_default(),
-            }),
-            range: None,
-        }))
+        let data = semantic_tokens::semantic_tokens(document, None);
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SemanticTokensRangeResult>> {
+        tracing::debug!(?params, "got semantic tokens range request");
+        let mut inner = self.inner.lock().await;
+        let Some(document) = inner.document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let data = semantic_tokens::semantic_tokens(document, Some(params.range));
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<SymbolInformation>>> {
+        tracing::debug!(?params, "got workspace symbol request");
+        let inner = self.inner.lock().await;
+        // open documents may have unsaved edits, so take their symbols from the editor's text as
+        // of their last analysis rather than analysing every open document on each query
+        let open: HashMap<_, _> = inner
+            .documents
+            .values()
+            .map(|document| (&document.uri, document.workspace_symbols.as_slice()))
+            .collect();
+        let files = inner
+            .index
+            .files()
+            .filter(|(uri, _)| !open.contains_key(*uri))
+            .chain(open.iter().map(|(uri, symbols)| (*uri, *symbols)));
+        Ok(Some(workspace::search(&params.query, files)))
     }
 
     async fn shutdown(&self) -> tower_lsp::jsonrpc::Result<()> {
@@ -401,6 +595,20 @@ enum TemplateLanguage {
     Json,
 }
 
+impl TemplateLanguage {
+    /// Map the `languageId` sent by the client onto a template language, if it names one
+    fn from_language_id(language_id: &str) -> Option<Self> {
+        let language_id = language_id.to_ascii_lowercase();
+        if language_id.contains("json") {
+            Some(TemplateLanguage::Json)
+        } else if language_id.contains("yaml") {
+            Some(TemplateLanguage::Yaml)
+        } else {
+            None
+        }
+    }
+}
+
 fn detect_template_language(filename: impl AsRef<Path>, text: &str) -> TemplateLanguage {
     // heuristics based on file extension
     if filename.as_ref().extension().and_then(|s| s.to_str()) == Some("json") {
@@ -433,11 +641,12 @@ async fn main() -> anyhow::Result<()> {
     let stdout = tokio::io::stdout();
 
     let (service, socket) = LspService::new(|client| ServerState {
-        _client: client,
+        client,
         inner: Arc::new(Mutex::new(ServerStateInner {
-            current_document: None,
-            jump_destinations: Vec::new(),
-            jump_sources: Vec::new(),
+            documents: HashMap::new(),
+            roots: Vec::new(),
+            watch_files: false,
+            index: WorkspaceIndex::default(),
         })),
     });
     Server::new(stdin, stdout, socket).serve(service).await;
@@ -445,114 +654,80 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn extract_resource_type(line: &str, position: Position) -> Option<String> {
-    // extract position of resource reference in line
-    for c in 0..line.len() - 1 {
-        let trailing = line[c..]
-            .chars()
-            .take_while(|&c| !c.is_whitespace() && c != '"')
-            .collect::<String>();
-        if trailing.starts_with("AWS::") {
-            let words: Vec<_> = trailing.split_whitespace().collect();
-            let name = words[0];
-            let range = (c, c + name.len());
-            let character = position.character as usize;
-            if character >= range.0 && character < range.1 {
-                return Some(trailing.to_string());
-            }
-        }
-    }
-    None
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tower_lsp::lsp_types::TextDocumentItem;
 
     #[test]
-    fn extract_simple() {
-        let line = "Type: AWS::SNS::Topic";
-        let pos = Position::new(0, 8);
-
-        let resource_type = extract_resource_type(line, pos).expect("extracting resource name");
-
-        assert_eq!(resource_type, "AWS::SNS::Topic");
-    }
-
-    #[test]
-    fn with_indent() {
-        let line = "        Type: AWS::SNS::Topic";
-        let pos = Position::new(0, 16);
-
-        let resource_type = extract_resource_type(line, pos).expect("extracting resource name");
-
-        assert_eq!(resource_type, "AWS::SNS::Topic");
-    }
-
-    #[test]
-    fn extract_not_over() {
-        let line = "Type: AWS::SNS::Topic";
-        let pos = Position::new(0, 0);
-
-        assert!(extract_resource_type(line, pos).is_none());
-    }
-
-    #[test]
-    fn extract_past_end() {
-        let line = "Type: AWS::SNS::Topic     ";
-        let pos = Position::new(0, u32::try_from(line.len() - 1).unwrap());
-
-        assert!(extract_resource_type(line, pos).is_none());
+    fn language_from_language_id() {
+        for (language_id, expected) in [
+            ("json", Some(TemplateLanguage::Json)),
+            ("jsonc", Some(TemplateLanguage::Json)),
+            ("yaml", Some(TemplateLanguage::Yaml)),
+            ("cloudformation.yaml", Some(TemplateLanguage::Yaml)),
+            ("plaintext", None),
+            ("", None),
+        ] {
+            assert_eq!(TemplateLanguage::from_language_id(language_id), expected);
+        }
     }
 
     #[test]
-    fn extract_from_json() {
-        let line = r#""Type": "AWS::SNS::Topic",""#;
-        let pos = Position::new(0, 10);
-
+    fn detect_language() {
+        assert_eq!(
+            detect_template_language("template.json", "Resources: {}"),
+            TemplateLanguage::Json
+        );
+        assert_eq!(
+            detect_template_language("template.yml", "{}"),
+            TemplateLanguage::Yaml
+        );
         assert_eq!(
-            extract_resource_type(line, pos),
-            Some("AWS::SNS::Topic".to_string())
+            detect_template_language("template.template", "  {\n  \"Resources\": {}}"),
+            TemplateLanguage::Json
+        );
+        assert_eq!(
+            detect_template_language("template.template", "Resources: {}"),
+            TemplateLanguage::Yaml
         );
     }
 
-    // tests for word under cursor
     #[test]
-    fn single_word_under_cursor() {
-        let content = "This is a test";
-        //             0123456789
-
-        for (c, expected) in [0, 1, 2, 3, 4, 5, 6, 7, 8, 9].into_iter().zip([
-            Some("This".to_string()),
-            Some("This".to_string()),
-            Some("This".to_string()),
-            Some("This".to_string()),
-            None,
-            Some("is".to_string()),
-            Some("is".to_string()),
-            None,
-            Some("a".to_string()),
-            None,
-        ]) {
-            let position = Position {
-                line: 0,
-                character: c,
-            };
-
-            assert_eq!(word_under_cursor(content, position).unwrap(), expected);
+    fn documents_are_analysed_independently() {
+        let mut inner = ServerStateInner {
+            documents: HashMap::new(),
+            roots: Vec::new(),
+            watch_files: false,
+            index: WorkspaceIndex::default(),
+        };
+        let first = Url::parse("file:///templates/first.yml").unwrap();
+        let second = Url::parse("file:///templates/second.yml").unwrap();
+        for (uri, text) in [
+            (&first, include_str!("../testdata/two_resources.yml")),
+            (&second, include_str!("../testdata/parameters.yml")),
+        ] {
+            inner.documents.insert(
+                uri.clone(),
+                Document::new(TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "yaml".to_string(),
+                    version: 0,
+                    text: text.to_string(),
+                }),
+            );
         }
-    }
 
-    #[test]
-    fn larger_template() {
-        let content = include_str!("../testdata/template.yml");
-        let position = Position {
-            line: 46,
-            character: 24,
+        let mut topic = |uri: &Url| {
+            inner
+                .document(uri)
+                .unwrap()
+                .jump_destinations
+                .iter()
+                .find(|d| d.name == "Topic")
+                .map(|d| d.span.start.line)
         };
-        assert_eq!(
-            word_under_cursor(content, position).unwrap(),
-            Some("TrustedAccounts".to_string())
-        );
+        assert_eq!(topic(&first), Some(1));
+        assert_eq!(topic(&second), Some(4));
     }
 }
@@ -0,0 +1,216 @@
+//! Hierarchical outline of a template for `textDocument/documentSymbol`
+use tower_lsp::lsp_types::{DocumentSymbol, SymbolKind};
+
+use crate::{
+    document::Document,
+    template::{Section, Span, Spanned},
+};
+
+/// The top level sections of the template, each with its logical IDs as children
+///
+/// Resources additionally list the keys of their `Properties`.
+pub(crate) fn document_symbols(document: &Document) -> Vec<DocumentSymbol> {
+    let template = &document.template;
+    let mut symbols = Vec::new();
+    push_section(document, &mut symbols, &template.metadata, |m| {
+        entry(
+            document,
+            &m.name,
+            m.definition.span,
+            SymbolKind::OBJECT,
+            None,
+        )
+    });
+    push_section(document, &mut symbols, &template.parameters, |p| {
+        let detail = p.parameter_type.as_ref().map(|t| t.value.clone());
+        entry(
+            document,
+            &p.name,
+            p.definition.span,
+            SymbolKind::VARIABLE,
+            detail,
+        )
+    });
+    push_section(document, &mut symbols, &template.mappings, |m| {
+        entry(
+            document,
+            &m.name,
+            m.definition.span,
+            SymbolKind::OBJECT,
+            None,
+        )
+    });
+    push_section(document, &mut symbols, &template.conditions, |c| {
+        entry(
+            document,
+            &c.name,
+            c.definition.span,
+            SymbolKind::BOOLEAN,
+            None,
+        )
+    });
+    push_section(document, &mut symbols, &template.rules, |r| {
+        entry(
+            document,
+            &r.name,
+            r.definition.span,
+            SymbolKind::EVENT,
+            None,
+        )
+    });
+    push_section(document, &mut symbols, &template.resources, |r| {
+        let detail = r.resource_type.as_ref().map(|t| t.value.clone());
+        let mut symbol = entry(
+            document,
+            &r.name,
+            r.definition.span,
+            SymbolKind::STRUCT,
+            detail,
+        );
+        let properties = r
+            .properties
+            .as_ref()
+            .and_then(|properties| properties.value.as_mapping())
+            .unwrap_or_default();
+        if !properties.is_empty() {
+            symbol.children = Some(
+                properties
+                    .iter()
+                    .map(|(key, value)| {
+                        entry(document, key, value.span, SymbolKind::PROPERTY, None)
+                    })
+                    .collect(),
+            );
+        }
+        symbol
+    });
+    push_section(document, &mut symbols, &template.outputs, |o| {
+        entry(
+            document,
+            &o.name,
+            o.definition.span,
+            SymbolKind::FIELD,
+            None,
+        )
+    });
+
+    // report the sections in document order
+    symbols.sort_by_key(|symbol| (symbol.range.start.line, symbol.range.start.character));
+    symbols
+}
+
+fn push_section<T>(
+    document: &Document,
+    symbols: &mut Vec<DocumentSymbol>,
+    section: &Option<Section<T>>,
+    entry: impl Fn(&T) -> DocumentSymbol,
+) {
+    let Some(section) = section else {
+        return;
+    };
+    let mut symbol = symbol(
+        document,
+        &section.key.value,
+        SymbolKind::NAMESPACE,
+        None,
+        section.span,
+        section.key.span,
+    );
+    symbol.children = Some(section.entries.iter().map(entry).collect());
+    symbols.push(symbol);
+}
+
+/// A named entry whose range covers its name and its definition
+fn entry(
+    document: &Document,
+    name: &Spanned<String>,
+    definition: Span,
+    kind: SymbolKind,
+    detail: Option<String>,
+) -> DocumentSymbol {
+    let range = Span {
+        start: name.span.start,
+        end: definition.end.max(name.span.end),
+    };
+    symbol(document, &name.value, kind, detail, range, name.span)
+}
+
+fn symbol(
+    document: &Document,
+    name: &str,
+    kind: SymbolKind,
+    detail: Option<String>,
+    range: Span,
+    selection: Span,
+) -> DocumentSymbol {
+    // `deprecated` is superseded by `tags`, but has no default
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: name.to_string(),
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: document.lsp_range(range),
+        selection_range: document.lsp_range(selection),
+        children: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Indented outline of name, kind and detail, for snapshots
+    fn outline(symbols: &[DocumentSymbol], depth: usize, out: &mut Vec<String>) {
+        for symbol in symbols {
+            let mut line = format!("{}{} {:?}", "  ".repeat(depth), symbol.name, symbol.kind);
+            if let Some(detail) = &symbol.detail {
+                line.push_str(&format!(" ({detail})"));
+            }
+            out.push(line);
+            outline(
+                symbol.children.as_deref().unwrap_or_default(),
+                depth + 1,
+                out,
+            );
+        }
+    }
+
+    #[test]
+    fn yaml_outline() {
+        let doc = test_document("stack", "yaml", include_str!("../testdata/outline.yml"));
+        let symbols = document_symbols(&doc);
+        let mut lines = Vec::new();
+        outline(&symbols, 0, &mut lines);
+        insta::assert_yaml_snapshot!(lines);
+
+        // every child lies within its parent, as required by the protocol
+        fn check(symbol: &DocumentSymbol) {
+            assert!(symbol.range.start <= symbol.selection_range.start);
+            assert!(symbol.selection_range.end <= symbol.range.end);
+            for child in symbol.children.iter().flatten() {
+                assert!(symbol.range.start <= child.range.start, "{}", child.name);
+                assert!(child.range.end <= symbol.range.end, "{}", child.name);
+                check(child);
+            }
+        }
+        symbols.iter().for_each(check);
+    }
+
+    #[test]
+    fn json_outline_matches_yaml() {
+        let names = |symbols: Vec<DocumentSymbol>| {
+            let mut lines = Vec::new();
+            outline(&symbols, 0, &mut lines);
+            lines
+        };
+        let yaml = test_document("stack", "yaml", include_str!("../testdata/sections.yml"));
+        let json = test_document("stack", "json", include_str!("../testdata/sections.json"));
+        assert_eq!(
+            names(document_symbols(&yaml)),
+            names(document_symbols(&json))
+        );
+    }
+}
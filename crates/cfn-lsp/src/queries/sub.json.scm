@@ -1,6 +0,0 @@
-(pair
-  key: (string
-         (string_content) @fn.tag)
-  value: (string
-           (string_content) @fn.target)
-    (#eq? @fn.tag "Fn::Sub")) @fn
@@ -0,0 +1,669 @@
+//! References from one part of a template to another, e.g. `!Ref Bucket` or `${Bucket.Arn}`
+//!
+//! References are read from the [`Template`] model, so the short and long forms of intrinsic
+//! functions in YAML and JSON all produce the same references.
+#[cfg(test)]
+use serde::Serialize;
+
+use crate::template::{Position, Span, Spanned, Template, Value};
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct Ref {
+    pub(crate) target: String,
+}
+
+/// A variable used in a `Fn::Sub` string, e.g. `${Bucket.Arn}`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct Sub {
+    /// The variable name, e.g. `Bucket` or `AWS::Region`
+    pub(crate) target: String,
+    /// The attribute following the logical ID, e.g. `Arn`
+    pub(crate) attribute: Option<Literal>,
+    /// Span of the key declaring the variable, if it is declared in the variable map of the two
+    /// argument form rather than being a template symbol
+    pub(crate) local: Option<Span>,
+}
+
+/// Part of a `Fn::Sub` string
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) enum SubPart<'a> {
+    /// Text that is output unchanged
+    Text(&'a str),
+    /// `${Name}` or `${Name.Attribute}`, with the byte offset of the name within the string
+    Variable {
+        name: &'a str,
+        attribute: Option<&'a str>,
+        offset: usize,
+    },
+    /// `${!Literal}`, which is output as `${Literal}`
+    Literal(&'a str),
+}
+
+/// Split a `Fn::Sub` string into its text, variables and literals
+pub(crate) fn parse_sub(text: &str) -> Vec<SubPart<'_>> {
+    let mut parts = Vec::new();
+    let mut offset = 0;
+    while let Some(index) = text[offset..].find("${") {
+        let open = offset + index;
+        let Some(length) = text[open..].find('}') else {
+            break;
+        };
+        let close = open + length;
+        if open > offset {
+            parts.push(SubPart::Text(&text[offset..open]));
+        }
+        let inner = &text[open + 2..close];
+        match inner.strip_prefix('!') {
+            Some(literal) => parts.push(SubPart::Literal(literal)),
+            None => {
+                let (name, attribute) = match inner.split_once('.') {
+                    Some((name, attribute)) => (name, Some(attribute)),
+                    None => (inner, None),
+                };
+                parts.push(SubPart::Variable {
+                    name,
+                    attribute,
+                    offset: open + 2,
+                });
+            }
+        }
+        offset = close + 1;
+    }
+    if offset < text.len() {
+        parts.push(SubPart::Text(&text[offset..]));
+    }
+    parts
+}
+
+/// Position of the byte offset within text that starts at `start`
+fn offset_position(start: Position, text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    match before.rfind('\n') {
+        Some(newline) => Position {
+            line: start.line + before.matches('\n').count(),
+            col: offset - newline - 1,
+        },
+        None => Position {
+            line: start.line,
+            col: start.col + offset,
+        },
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct GetAtt {
+    /// The logical ID of the resource
+    pub(crate) target: String,
+    /// The attribute name, e.g. `Arn` or `Endpoint.Address`, if it is given as a string
+    pub(crate) attribute: Option<Literal>,
+}
+
+/// A string argument of an intrinsic function, with the span of the text excluding any quotes
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct Literal {
+    pub(crate) name: String,
+    pub(crate) span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct FindInMap {
+    /// The mapping name
+    pub(crate) target: String,
+    /// The top level key, unless it is computed by a nested function such as `!Ref AWS::Region`
+    pub(crate) top_level_key: Option<Literal>,
+    /// The second level key, unless it is computed by a nested function
+    pub(crate) second_level_key: Option<Literal>,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct If {
+    pub(crate) target: String,
+}
+
+/// A reference to a named condition, e.g. `Condition: IsProduction` or `!Condition IsProduction`
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct Condition {
+    pub(crate) target: String,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct DependsOn {
+    pub(crate) target: String,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) struct Reference {
+    pub(crate) typ: ReferenceType,
+    /// Span of the referenced name
+    pub(crate) span: Span,
+}
+
+impl Reference {
+    /// Whether the position falls within the reference, including its end
+    pub(crate) fn contains(&self, position: Position) -> bool {
+        self.span.contains(position)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) enum ReferenceType {
+    Ref(Ref),
+    Sub(Sub),
+    GetAtt(GetAtt),
+    FindInMap(FindInMap),
+    If(If),
+    DependsOn(DependsOn),
+    Condition(Condition),
+}
+
+/// Every reference in the template, in document order
+///
+/// The template text is needed to find the variables of `Fn::Sub` strings, whose parsed value
+/// loses the positions of escaped and folded text.
+pub(crate) fn references(template: &Template, content: &str) -> Vec<Reference> {
+    let mut collector = Collector {
+        content,
+        line_starts: std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+            .collect(),
+        references: Vec::new(),
+    };
+
+    for metadata in template.metadata.iter().flat_map(|s| &s.entries) {
+        collector.value(&metadata.definition, false);
+    }
+    for parameter in template.parameters.iter().flat_map(|s| &s.entries) {
+        collector.value(&parameter.definition, false);
+    }
+    for condition in template.conditions.iter().flat_map(|s| &s.entries) {
+        collector.value(&condition.definition, true);
+    }
+    for rule in template.rules.iter().flat_map(|s| &s.entries) {
+        collector.value(&rule.definition, false);
+    }
+    for resource in template.resources.iter().flat_map(|s| &s.entries) {
+        collector.value(&resource.definition, false);
+        collector.condition(resource.condition.as_ref());
+        for target in &resource.depends_on {
+            collector.push(
+                ReferenceType::DependsOn(DependsOn {
+                    target: target.value.clone(),
+                }),
+                target.span,
+            );
+        }
+    }
+    for output in template.outputs.iter().flat_map(|s| &s.entries) {
+        collector.value(&output.definition, false);
+        collector.condition(output.condition.as_ref());
+    }
+
+    let mut references = collector.references;
+    references.sort_by_key(|reference| reference.span.start);
+    references
+}
+
+/// The string given by a scalar argument, or `None` if the argument is computed by a nested
+/// function
+fn literal(value: &Spanned<Value>) -> Option<Literal> {
+    match &value.value {
+        Value::String(name) | Value::Number(name) => Some(Literal {
+            name: name.clone(),
+            span: value.span,
+        }),
+        _ => None,
+    }
+}
+
+struct Collector<'a> {
+    content: &'a str,
+    /// Byte offset of the start of every line of the content
+    line_starts: Vec<usize>,
+    references: Vec<Reference>,
+}
+
+impl Collector<'_> {
+    fn push(&mut self, typ: ReferenceType, span: Span) {
+        self.references.push(Reference { typ, span });
+    }
+
+    /// The `Condition` attribute of a resource or output
+    fn condition(&mut self, condition: Option<&Spanned<String>>) {
+        if let Some(condition) = condition {
+            self.push(
+                ReferenceType::Condition(Condition {
+                    target: condition.value.clone(),
+                }),
+                condition.span,
+            );
+        }
+    }
+
+    /// Collect the references within a value, which is part of a condition expression if
+    /// `in_condition` is set, where `Condition: Name` refers to another condition
+    fn value(&mut self, value: &Spanned<Value>, in_condition: bool) {
+        match &value.value {
+            Value::Sequence(items) => {
+                for item in items {
+                    self.value(item, in_condition);
+                }
+            }
+            Value::Mapping(pairs) => {
+                if let [(key, argument)] = pairs.as_slice()
+                    && key.value == "Condition"
+                    && in_condition
+                    && let Some(target) = literal(argument)
+                {
+                    self.push(
+                        ReferenceType::Condition(Condition {
+                            target: target.name,
+                        }),
+                        target.span,
+                    );
+                } else if let Some((function, argument)) = value.value.as_intrinsic() {
+                    self.intrinsic(&function.value, argument);
+                    let in_condition =
+                        matches!(function.value.as_str(), "Fn::And" | "Fn::Or" | "Fn::Not");
+                    self.value(argument, in_condition);
+                } else {
+                    for (_, value) in pairs {
+                        self.value(value, false);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Collect the references made by an intrinsic function itself; references nested in its
+    /// argument are collected separately
+    fn intrinsic(&mut self, function: &str, argument: &Spanned<Value>) {
+        let items = match &argument.value {
+            Value::Sequence(items) => items.as_slice(),
+            _ => &[],
+        };
+        match function {
+            "Ref" => {
+                if let Some(target) = literal(argument) {
+                    self.push(
+                        ReferenceType::Ref(Ref {
+                            target: target.name,
+                        }),
+                        target.span,
+                    );
+                }
+            }
+            "Fn::GetAtt" => self.get_att(argument, items),
+            "Fn::Sub" => match items {
+                [] => self.sub(argument, None),
+                [text, ..] => self.sub(text, items.get(1)),
+            },
+            "Fn::FindInMap" => {
+                if let Some(target) = items.first().and_then(literal) {
+                    self.push(
+                        ReferenceType::FindInMap(FindInMap {
+                            target: target.name,
+                            top_level_key: items.get(1).and_then(literal),
+                            second_level_key: items.get(2).and_then(literal),
+                        }),
+                        target.span,
+                    );
+                }
+            }
+            "Fn::If" => {
+                if let Some(target) = items.first().and_then(literal) {
+                    self.push(
+                        ReferenceType::If(If {
+                            target: target.name,
+                        }),
+                        target.span,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `Fn::GetAtt: [Name, Attribute]` or `!GetAtt Name.Attribute`, where the attribute may itself
+    /// contain dots
+    fn get_att(&mut self, argument: &Spanned<Value>, items: &[Spanned<Value>]) {
+        if let [target, rest @ ..] = items {
+            if let Some(target) = literal(target) {
+                self.push(
+                    ReferenceType::GetAtt(GetAtt {
+                        target: target.name,
+                        attribute: rest.first().and_then(literal),
+                    }),
+                    target.span,
+                );
+            }
+            return;
+        }
+        let Some(value) = literal(argument) else {
+            return;
+        };
+        let start = value.span.start;
+        let (target, attribute) = match value.name.split_once('.') {
+            Some((target, attribute)) => {
+                let attribute = Literal {
+                    name: attribute.to_string(),
+                    span: Span {
+                        start: Position {
+                            line: start.line,
+                            col: start.col + target.len() + 1,
+                        },
+                        end: value.span.end,
+                    },
+                };
+                (target, Some(attribute))
+            }
+            None => (value.name.as_str(), None),
+        };
+        self.push(
+            ReferenceType::GetAtt(GetAtt {
+                target: target.to_string(),
+                attribute,
+            }),
+            Span {
+                start,
+                end: Position {
+                    line: start.line,
+                    col: start.col + target.len(),
+                },
+            },
+        );
+    }
+
+    /// The variables of a `Fn::Sub` string, along with the variable map of the two argument form
+    fn sub(&mut self, text: &Spanned<Value>, variables: Option<&Spanned<Value>>) {
+        if !matches!(text.value, Value::String(_)) {
+            return;
+        }
+        let variables = variables
+            .and_then(|variables| variables.value.as_mapping())
+            .unwrap_or_default();
+        // quotes, block scalar indicators and indentation never contain placeholders, so the
+        // source text of the string can be tokenized as is
+        let start = text.span.start;
+        let source = &self.content[self.offset(start)..self.offset(text.span.end)];
+        for part in parse_sub(source) {
+            let SubPart::Variable {
+                name,
+                attribute,
+                offset,
+            } = part
+            else {
+                continue;
+            };
+            let local = variables
+                .iter()
+                .find(|(key, _)| key.value == name)
+                .map(|(key, _)| key.span);
+            // the attribute follows the name and the dot
+            let end = offset + name.len();
+            let attribute = attribute.map(|attribute| Literal {
+                name: attribute.to_string(),
+                span: Span {
+                    start: offset_position(start, source, end + 1),
+                    end: offset_position(start, source, end + 1 + attribute.len()),
+                },
+            });
+            self.push(
+                ReferenceType::Sub(Sub {
+                    target: name.to_string(),
+                    attribute,
+                    local,
+                }),
+                Span {
+                    start: offset_position(start, source, offset),
+                    end: offset_position(start, source, end),
+                },
+            );
+        }
+    }
+
+    /// Byte offset of a position within the content
+    fn offset(&self, position: Position) -> usize {
+        let line_start = self
+            .line_starts
+            .get(position.line)
+            .copied()
+            .unwrap_or(self.content.len());
+        (line_start + position.col).min(self.content.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TemplateLanguage;
+
+    /// The references in a test template that match the filter
+    fn extract(
+        name: &str,
+        language: TemplateLanguage,
+        filter: impl Fn(&ReferenceType) -> bool,
+    ) -> Vec<Reference> {
+        let contents = std::fs::read_to_string(format!("testdata/{name}")).unwrap();
+        let template = Template::parse(&contents, language).unwrap();
+        references(&template, &contents)
+            .into_iter()
+            .filter(|reference| filter(&reference.typ))
+            .collect()
+    }
+
+    fn refs(name: &str, language: TemplateLanguage) -> Vec<Reference> {
+        extract(name, language, |typ| matches!(typ, ReferenceType::Ref(_)))
+    }
+
+    fn subs(name: &str, language: TemplateLanguage) -> Vec<Reference> {
+        extract(name, language, |typ| matches!(typ, ReferenceType::Sub(_)))
+    }
+
+    fn getatts(name: &str, language: TemplateLanguage) -> Vec<Reference> {
+        extract(name, language, |typ| {
+            matches!(typ, ReferenceType::GetAtt(_))
+        })
+    }
+
+    fn dependsons(name: &str, language: TemplateLanguage) -> Vec<Reference> {
+        extract(name, language, |typ| {
+            matches!(typ, ReferenceType::DependsOn(_))
+        })
+    }
+
+    fn conditions(name: &str, language: TemplateLanguage) -> Vec<Reference> {
+        extract(name, language, |typ| {
+            matches!(typ, ReferenceType::Condition(_))
+        })
+    }
+
+    mod refs {
+        use super::*;
+
+        #[test]
+        fn extract_from_outputs() {
+            insta::assert_yaml_snapshot!(refs("outputs.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn extract_from_parameters() {
+            insta::assert_yaml_snapshot!(refs("parameters.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn extract_from_two_resources() {
+            insta::assert_yaml_snapshot!(refs("two_resources.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn extract_from_template() {
+            insta::assert_yaml_snapshot!(refs("template.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn quoted_targets_are_unquoted() {
+            let refs = refs("rename.yml", TemplateLanguage::Yaml);
+            let quoted = refs.iter().find(|r| r.span.start.line == 31).unwrap();
+            assert_eq!(
+                quoted.typ,
+                ReferenceType::Ref(Ref {
+                    target: "Topic".to_string()
+                })
+            );
+            assert_eq!(quoted.span.start.col, 17);
+        }
+    }
+
+    mod subs {
+        use super::*;
+
+        #[test]
+        fn extract_from_subs() {
+            insta::assert_yaml_snapshot!(subs("subs.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn extract_from_sub_syntaxes() {
+            insta::assert_yaml_snapshot!(subs("sub_syntaxes.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn parse_sub_strings() {
+            insta::assert_yaml_snapshot!(parse_sub(
+                "arn:${AWS::Partition}:s3:::${Bucket.Arn}/${!Literal}${Name}${Unclosed"
+            ));
+        }
+
+        #[test]
+        fn multiline_sub_positions() {
+            let start = Position { line: 3, col: 10 };
+            let text = "#!/bin/bash\necho ${Name}";
+            assert_eq!(
+                offset_position(start, text, 2),
+                Position { line: 3, col: 12 }
+            );
+            assert_eq!(
+                offset_position(start, text, 19),
+                Position { line: 4, col: 7 }
+            );
+        }
+    }
+
+    mod getatts {
+        use super::*;
+
+        #[test]
+        fn extract_from_getatt() {
+            insta::assert_yaml_snapshot!(getatts("getatt.yml", TemplateLanguage::Yaml));
+        }
+    }
+
+    mod findinmaps {
+        use super::*;
+
+        #[test]
+        fn extract_from_findinmap() {
+            insta::assert_yaml_snapshot!(extract(
+                "findinmap.yml",
+                TemplateLanguage::Yaml,
+                |typ| matches!(typ, ReferenceType::FindInMap(_))
+            ));
+        }
+    }
+
+    mod ifs {
+        use super::*;
+
+        #[test]
+        fn extract_from_if() {
+            insta::assert_yaml_snapshot!(extract("if.yml", TemplateLanguage::Yaml, |typ| {
+                matches!(typ, ReferenceType::If(_))
+            }));
+        }
+    }
+
+    mod conditions {
+        use super::*;
+
+        #[test]
+        fn extract_from_conditions() {
+            insta::assert_yaml_snapshot!(conditions("conditions.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn extract_from_json_conditions() {
+            insta::assert_yaml_snapshot!(conditions("conditions.json", TemplateLanguage::Json));
+        }
+    }
+
+    mod dependsons {
+        use super::*;
+
+        #[test]
+        fn extract_from_dependson() {
+            insta::assert_yaml_snapshot!(dependsons("dependson.yml", TemplateLanguage::Yaml));
+        }
+
+        #[test]
+        fn flow_sequence_items() {
+            let targets: Vec<_> = dependsons("sections.yml", TemplateLanguage::Yaml)
+                .into_iter()
+                .map(|reference| reference.typ)
+                .collect();
+            assert_eq!(
+                targets,
+                [ReferenceType::DependsOn(DependsOn {
+                    target: "Queue".to_string()
+                })]
+            );
+        }
+    }
+
+    mod json {
+        use super::*;
+
+        #[test]
+        fn extract_refs_from_json() {
+            insta::assert_yaml_snapshot!(refs("template.json", TemplateLanguage::Json));
+        }
+
+        #[test]
+        fn extract_subs_from_json() {
+            insta::assert_yaml_snapshot!(subs("template.json", TemplateLanguage::Json));
+        }
+
+        #[test]
+        fn extract_sub_syntaxes_from_json() {
+            insta::assert_yaml_snapshot!(subs("sub_syntaxes.json", TemplateLanguage::Json));
+        }
+
+        #[test]
+        fn extract_getatts_from_json() {
+            insta::assert_yaml_snapshot!(getatts("template.json", TemplateLanguage::Json));
+        }
+
+        #[test]
+        fn extract_dependsons_from_json() {
+            insta::assert_yaml_snapshot!(dependsons("template.json", TemplateLanguage::Json));
+        }
+
+        #[test]
+        fn extract_all_from_json() {
+            insta::assert_yaml_snapshot!(extract("template.json", TemplateLanguage::Json, |_| {
+                true
+            }));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Renaming resources, parameters, mappings, conditions and outputs along with their usages
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::{
+    document::Document,
+    symbols::{self, Occurrence},
+};
+
+/// Range and current name of the symbol under the cursor, if it can be renamed
+pub(crate) fn prepare_rename(document: &Document, position: Position) -> Option<(Range, String)> {
+    let occurrences = symbols::occurrences(document);
+    let occurrence = renameable(document, &occurrences, position)?;
+    Some((document.lsp_range(occurrence.span), occurrence.name.clone()))
+}
+
+/// Edits renaming the symbol under the cursor, its declaration and every usage to `new_name`
+///
+/// Returns `None` if there is no symbol declared in the template under the cursor.
+pub(crate) fn rename(
+    document: &Document,
+    position: Position,
+    new_name: &str,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let occurrences = symbols::occurrences(document);
+    let Some(occurrence) = renameable(document, &occurrences, position) else {
+        return Ok(None);
+    };
+    let Some(declaration) = symbols::declaration(document, occurrence) else {
+        return Ok(None);
+    };
+
+    if new_name.is_empty() || !new_name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        anyhow::bail!("`{new_name}` is not a valid logical ID, which must be alphanumeric");
+    }
+    if new_name == declaration.name {
+        return Ok(Some(Vec::new()));
+    }
+    let namespace = symbols::namespace(declaration.r#type);
+    if document
+        .jump_destinations
+        .iter()
+        .any(|d| d.name == new_name && namespace.contains(&d.r#type))
+    {
+        anyhow::bail!("`{new_name}` is already declared in the template");
+    }
+
+    let edits = symbols::occurrences_of(&occurrences, declaration)
+        .map(|occurrence| TextEdit {
+            range: document.lsp_range(occurrence.span),
+            new_text: new_name.to_string(),
+        })
+        .collect();
+    Ok(Some(edits))
+}
+
+/// The occurrence under the cursor if it refers to a symbol declared in the template
+fn renameable<'o>(
+    document: &Document,
+    occurrences: &'o [Occurrence],
+    position: Position,
+) -> Option<&'o Occurrence> {
+    let occurrence = symbols::occurrence_at(document, occurrences, position)?;
+    // pseudo parameters and undeclared names cannot be renamed
+    symbols::declaration(document, occurrence)?;
+    Some(occurrence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    fn document() -> Document {
+        test_document("rename.yml", "yaml", include_str!("../testdata/rename.yml"))
+    }
+
+    /// Apply the edits to the text, assuming they do not overlap
+    fn apply(text: &str, mut edits: Vec<TextEdit>) -> String {
+        edits.sort_by_key(|e| (e.range.start.line, e.range.start.character));
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        for edit in edits.iter().rev() {
+            let line = &mut lines[edit.range.start.line as usize];
+            line.replace_range(
+                edit.range.start.character as usize..edit.range.end.character as usize,
+                &edit.new_text,
+            );
+        }
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn rename_resource() {
+        let doc = document();
+        // the `Topic` in `!GetAtt Topic.TopicName`
+        let edits = rename(&doc, Position::new(16, 40), "Notifications")
+            .unwrap()
+            .unwrap();
+        assert_eq!(edits.len(), 7);
+        insta::assert_snapshot!(apply(&doc.text, edits));
+    }
+
+    #[test]
+    fn rename_condition() {
+        let doc = document();
+        let edits = rename(&doc, Position::new(5, 4), "IsProduction")
+            .unwrap()
+            .unwrap();
+        let text = apply(&doc.text, edits);
+        assert!(!text.contains("IsProd,") && !text.contains("IsProd\n"));
+        assert_eq!(text.matches("IsProduction").count(), 4);
+    }
+
+    #[test]
+    fn prepare_rename_ranges() {
+        let doc = document();
+        let (range, name) = prepare_rename(&doc, Position::new(31, 18)).unwrap();
+        assert_eq!(name, "Topic");
+        // excludes the quotes around the name
+        assert_eq!(range.start, Position::new(31, 17));
+        assert_eq!(range.end, Position::new(31, 22));
+
+        let (_, name) = prepare_rename(&doc, Position::new(26, 40)).unwrap();
+        assert_eq!(name, "Environment");
+
+        // the attribute name, a literal and a pseudo parameter are not symbols
+        assert_eq!(prepare_rename(&doc, Position::new(16, 50)), None);
+        assert_eq!(prepare_rename(&doc, Position::new(26, 57)), None);
+        assert_eq!(prepare_rename(&doc, Position::new(2, 10)), None);
+    }
+
+    #[test]
+    fn refuses_existing_names() {
+        let doc = document();
+        let topic = Position::new(8, 3);
+        assert!(rename(&doc, topic, "Queue").is_err());
+        // parameters share a namespace with resources
+        assert!(rename(&doc, topic, "Environment").is_err());
+        assert!(rename(&doc, topic, "Not-Valid").is_err());
+        // conditions do not
+        assert!(rename(&doc, topic, "IsProd").unwrap().is_some());
+    }
+}
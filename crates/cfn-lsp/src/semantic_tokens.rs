@@ -0,0 +1,434 @@
+//! Semantic highlighting of intrinsic functions and template symbols for
+//! `textDocument/semanticTokens`
+use cfn_lsp_schema::{PathSegment, ResourceSchema, resource_schema};
+use tower_lsp::lsp_types::{
+    Position as LspPosition, Range, SemanticToken, SemanticTokenModifier, SemanticTokenType,
+    SemanticTokensLegend,
+};
+
+use crate::{
+    destinations::JumpDestinationType,
+    document::Document,
+    references::ReferenceType,
+    symbols,
+    template::{Position, Span, Spanned, Value},
+};
+
+/// Token types, in the order of the legend sent to the client
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::OPERATOR,
+];
+
+/// Token modifiers, in the order of the bits of the legend sent to the client
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+    SemanticTokenModifier::DEPRECATED,
+];
+
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+const DEFAULT_LIBRARY: u32 = 1 << 2;
+const DEPRECATED: u32 = 1 << 3;
+
+pub(crate) fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A classified range of a single line
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    span: Span,
+    token_type: SemanticTokenType,
+    modifiers: u32,
+}
+
+/// Tokens of the whole document, or only those overlapping the range, encoded relative to each
+/// other as the protocol requires
+pub(crate) fn semantic_tokens(document: &Document, range: Option<Range>) -> Vec<SemanticToken> {
+    let mut tokens = Vec::new();
+    intrinsic_functions(document, &mut tokens);
+    template_symbols(document, &mut tokens);
+    resource_types(document, &mut tokens);
+
+    tokens.retain(|token| token.span.start.line == token.span.end.line);
+    if let Some(range) = range {
+        let (start, end) = (
+            document.text_position(range.start),
+            document.text_position(range.end),
+        );
+        tokens.retain(|token| token.span.end > start && token.span.start < end);
+    }
+    tokens.sort_by_key(|token| (token.span.start, token.span.end));
+    // the protocol does not allow overlapping tokens, so the first of any overlap wins
+    tokens.dedup_by(|token, previous| token.span.start < previous.span.end);
+    encode(document, &tokens)
+}
+
+/// Encode the sorted tokens, whose columns and lengths the protocol counts in UTF-16 code units
+fn encode(document: &Document, tokens: &[Token]) -> Vec<SemanticToken> {
+    let mut previous = LspPosition::new(0, 0);
+    tokens
+        .iter()
+        .map(|token| {
+            let start = document.lsp_position(token.span.start);
+            let end = document.lsp_position(token.span.end);
+            let delta_start = if start.line == previous.line {
+                start.character - previous.character
+            } else {
+                start.character
+            };
+            let encoded = SemanticToken {
+                delta_line: start.line - previous.line,
+                delta_start,
+                length: end.character - start.character,
+                token_type: TOKEN_TYPES
+                    .iter()
+                    .position(|t| *t == token.token_type)
+                    .expect("token type missing from the legend")
+                    as u32,
+                token_modifiers_bitset: token.modifiers,
+            };
+            previous = start;
+            encoded
+        })
+        .collect()
+}
+
+/// The names of intrinsic function calls, e.g. `!Ref` or `Fn::GetAtt`
+fn intrinsic_functions(document: &Document, tokens: &mut Vec<Token>) {
+    let template = &document.template;
+    // condition expressions may refer to other conditions with `Condition: Name`
+    for condition in template.conditions.iter().flat_map(|s| &s.entries) {
+        function_calls(&condition.definition, true, tokens);
+    }
+    let definitions = (template.metadata.iter().flat_map(|s| &s.entries)).map(|m| &m.definition);
+    let definitions = definitions
+        .chain(
+            template
+                .rules
+                .iter()
+                .flat_map(|s| &s.entries)
+                .map(|r| &r.definition),
+        )
+        .chain(
+            template
+                .resources
+                .iter()
+                .flat_map(|s| &s.entries)
+                .map(|r| &r.definition),
+        )
+        .chain(
+            template
+                .outputs
+                .iter()
+                .flat_map(|s| &s.entries)
+                .map(|o| &o.definition),
+        );
+    for definition in definitions {
+        function_calls(definition, false, tokens);
+    }
+}
+
+fn function_calls(value: &Spanned<Value>, in_function: bool, tokens: &mut Vec<Token>) {
+    if let Some((function, argument)) = value.value.as_intrinsic() {
+        tokens.push(Token {
+            span: function.span,
+            token_type: SemanticTokenType::FUNCTION,
+            modifiers: DEFAULT_LIBRARY,
+        });
+        function_calls(argument, true, tokens);
+        return;
+    }
+    match &value.value {
+        // `Condition` is only a function inside a condition expression, elsewhere it is the
+        // attribute of a resource or output
+        Value::Mapping(pairs) => match pairs.as_slice() {
+            [(key, argument)] if in_function && key.value == "Condition" => {
+                tokens.push(Token {
+                    span: key.span,
+                    token_type: SemanticTokenType::FUNCTION,
+                    modifiers: DEFAULT_LIBRARY,
+                });
+                function_calls(argument, true, tokens);
+            }
+            pairs => {
+                for (_, value) in pairs {
+                    function_calls(value, in_function, tokens);
+                }
+            }
+        },
+        Value::Sequence(items) => {
+            for item in items {
+                function_calls(item, in_function, tokens);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Declarations of and references to logical IDs, parameters, conditions and mappings, along
+/// with the attributes and placeholders around references
+fn template_symbols(document: &Document, tokens: &mut Vec<Token>) {
+    for occurrence in symbols::occurrences(document) {
+        let r#type = if occurrence.is_declaration {
+            occurrence.types.first().copied()
+        } else {
+            symbols::declaration(document, &occurrence).map(|declaration| declaration.r#type)
+        };
+        let (token_type, modifiers) = match r#type {
+            Some(r#type) => {
+                let Some(token_type) = symbol_type(r#type) else {
+                    continue;
+                };
+                let modifiers = if occurrence.is_declaration {
+                    DECLARATION
+                } else {
+                    0
+                };
+                (token_type, modifiers)
+            }
+            // pseudo parameters are never declared
+            None if occurrence.name.starts_with("AWS::")
+                && occurrence.types.contains(&JumpDestinationType::Parameter) =>
+            {
+                (SemanticTokenType::VARIABLE, READONLY | DEFAULT_LIBRARY)
+            }
+            // unresolved references are reported as diagnostics instead
+            None => continue,
+        };
+        tokens.push(Token {
+            span: occurrence.span,
+            token_type,
+            modifiers,
+        });
+    }
+
+    for reference in &document.jump_sources {
+        let Span { start, end } = reference.span;
+        match &reference.typ {
+            ReferenceType::GetAtt(getatt) => {
+                if let Some(attribute) = &getatt.attribute {
+                    tokens.push(Token {
+                        span: attribute.span,
+                        token_type: SemanticTokenType::PROPERTY,
+                        modifiers: 0,
+                    });
+                }
+            }
+            ReferenceType::Sub(sub) => {
+                if let Some(local) = sub.local {
+                    for (span, modifiers) in [(reference.span, 0), (local, DECLARATION)] {
+                        tokens.push(Token {
+                            span,
+                            token_type: SemanticTokenType::VARIABLE,
+                            modifiers,
+                        });
+                    }
+                }
+                let mut close = end;
+                if let Some(attribute) = &sub.attribute {
+                    close = attribute.span.end;
+                    tokens.push(Token {
+                        span: attribute.span,
+                        token_type: SemanticTokenType::PROPERTY,
+                        modifiers: 0,
+                    });
+                }
+                // the `${` and `}` around the placeholder
+                for span in [
+                    Span {
+                        start: Position {
+                            line: start.line,
+                            col: start.col.saturating_sub(2),
+                        },
+                        end: start,
+                    },
+                    Span {
+                        start: close,
+                        end: Position {
+                            line: close.line,
+                            col: close.col + 1,
+                        },
+                    },
+                ] {
+                    tokens.push(Token {
+                        span,
+                        token_type: SemanticTokenType::OPERATOR,
+                        modifiers: 0,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn symbol_type(r#type: JumpDestinationType) -> Option<SemanticTokenType> {
+    match r#type {
+        JumpDestinationType::Resource => Some(SemanticTokenType::CLASS),
+        JumpDestinationType::Parameter => Some(SemanticTokenType::PARAMETER),
+        JumpDestinationType::Condition => Some(SemanticTokenType::ENUM_MEMBER),
+        JumpDestinationType::Mapping => Some(SemanticTokenType::NAMESPACE),
+        // outputs cannot be referenced from within the template
+        JumpDestinationType::Output => None,
+    }
+}
+
+/// Resource type strings, and the deprecated property keys of resources with a known type
+fn resource_types(document: &Document, tokens: &mut Vec<Token>) {
+    for resource in document.template.resources.iter().flat_map(|s| &s.entries) {
+        let Some(resource_type) = &resource.resource_type else {
+            continue;
+        };
+        tokens.push(Token {
+            span: resource_type.span,
+            token_type: SemanticTokenType::TYPE,
+            modifiers: 0,
+        });
+        let (Some(properties), Ok(schema)) =
+            (&resource.properties, resource_schema(&resource_type.value))
+        else {
+            continue;
+        };
+        deprecated_keys(&schema, properties, &mut Vec::new(), tokens);
+    }
+}
+
+fn deprecated_keys<'v>(
+    schema: &ResourceSchema,
+    value: &'v Spanned<Value>,
+    path: &mut Vec<PathSegment<'v>>,
+    tokens: &mut Vec<Token>,
+) {
+    if value.value.as_intrinsic().is_some() {
+        return;
+    }
+    match &value.value {
+        Value::Mapping(pairs) => {
+            for (key, value) in pairs {
+                path.push(PathSegment::Key(&key.value));
+                if schema.access(path).deprecated {
+                    tokens.push(Token {
+                        span: key.span,
+                        token_type: SemanticTokenType::PROPERTY,
+                        modifiers: DEPRECATED,
+                    });
+                }
+                deprecated_keys(schema, value, path, tokens);
+                path.pop();
+            }
+        }
+        Value::Sequence(items) => {
+            path.push(PathSegment::Item);
+            for item in items {
+                deprecated_keys(schema, item, path, tokens);
+            }
+            path.pop();
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    /// Decode the tokens back into the text, type and modifiers of each, for snapshots
+    ///
+    /// Columns are UTF-16 code units, as in the protocol.
+    fn decode(text: &str, tokens: &[SemanticToken]) -> Vec<String> {
+        let lines: Vec<Vec<u16>> = text.lines().map(|l| l.encode_utf16().collect()).collect();
+        let (mut line, mut col) = (0, 0);
+        tokens
+            .iter()
+            .map(|token| {
+                if token.delta_line > 0 {
+                    col = 0;
+                }
+                line += token.delta_line as usize;
+                col += token.delta_start as usize;
+                let source =
+                    String::from_utf16_lossy(&lines[line][col..col + token.length as usize]);
+                let mut decoded = format!(
+                    "{}:{} {source} {}",
+                    line,
+                    col,
+                    TOKEN_TYPES[token.token_type as usize].as_str()
+                );
+                for (bit, modifier) in TOKEN_MODIFIERS.iter().enumerate() {
+                    if token.token_modifiers_bitset & (1 << bit) != 0 {
+                        decoded.push_str(&format!(" +{}", modifier.as_str()));
+                    }
+                }
+                decoded
+            })
+            .collect()
+    }
+
+    #[test]
+    fn yaml_tokens() {
+        let text = include_str!("../testdata/semantic_tokens.yml");
+        let doc = test_document("stack.yml", "yaml", text);
+        insta::assert_yaml_snapshot!(decode(text, &semantic_tokens(&doc, None)));
+    }
+
+    #[test]
+    fn json_tokens() {
+        let text = r#"{
+  "Parameters": {"Name": {"Type": "String"}},
+  "Resources": {
+    "Bucket": {
+      "Type": "AWS::S3::Bucket",
+      "Properties": {"BucketName": {"Fn::Sub": "${Name}-${AWS::AccountId}"}}
+    }
+  }
+}"#;
+        let doc = test_document("stack.json", "json", text);
+        assert_eq!(
+            decode(text, &semantic_tokens(&doc, None)),
+            [
+                "1:18 Name parameter +declaration",
+                "3:5 Bucket class +declaration",
+                "4:15 AWS::S3::Bucket type",
+                "5:37 Fn::Sub function +defaultLibrary",
+                "5:48 ${ operator",
+                "5:50 Name parameter",
+                "5:54 } operator",
+                "5:56 ${ operator",
+                "5:58 AWS::AccountId variable +readonly +defaultLibrary",
+                "5:72 } operator",
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_in_range() {
+        let text = include_str!("../testdata/semantic_tokens.yml");
+        let doc = test_document("stack.yml", "yaml", text);
+        let range = Range {
+            start: tower_lsp::lsp_types::Position::new(14, 0),
+            end: tower_lsp::lsp_types::Position::new(16, 0),
+        };
+        assert_eq!(
+            decode(text, &semantic_tokens(&doc, Some(range))),
+            [
+                "14:2 Topic class +declaration",
+                "15:10 AWS::SNS::Topic type"
+            ]
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Occurrences of template symbols (logical IDs, parameters, mappings and conditions), built from
+//! the jump destinations and jump sources of a document
+use tower_lsp::lsp_types::Position as LspPosition;
+use tree_sitter::Node;
+
+use crate::{
+    destinations::{JumpDestination, JumpDestinationType},
+    document::Document,
+    references::{Literal, ReferenceType},
+    template::{Position, Span, Spanned},
+};
+
+/// Symbol types a `Ref` (or a `${}` placeholder in a `Sub` string) may refer to
+const REF_TARGETS: &[JumpDestinationType] = &[
+    JumpDestinationType::Resource,
+    JumpDestinationType::Parameter,
+];
+
+/// A declaration of, or reference to, a symbol
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Occurrence {
+    pub(crate) name: String,
+    /// Range of the name only, excluding quotes and any `.Attribute` suffix
+    pub(crate) span: Span,
+    /// Symbol types the name may refer to
+    pub(crate) types: &'static [JumpDestinationType],
+    pub(crate) is_declaration: bool,
+}
+
+impl Occurrence {
+    fn refers_to(&self, destination: &JumpDestination) -> bool {
+        self.name == destination.name && self.types.contains(&destination.r#type)
+    }
+}
+
+/// Every declaration and reference of a symbol in the document, in no particular order
+pub(crate) fn occurrences(document: &Document) -> Vec<Occurrence> {
+    let mut occurrences: Vec<_> = document
+        .jump_destinations
+        .iter()
+        .map(|destination| Occurrence {
+            name: destination.name.clone(),
+            span: destination.span,
+            types: declared_type(destination.r#type),
+            is_declaration: true,
+        })
+        .collect();
+
+    for reference in &document.jump_sources {
+        let (target, types): (_, &'static [JumpDestinationType]) = match &reference.typ {
+            ReferenceType::Ref(r) => (&r.target, REF_TARGETS),
+            ReferenceType::GetAtt(g) => (&g.target, &[JumpDestinationType::Resource]),
+            ReferenceType::DependsOn(d) => (&d.target, &[JumpDestinationType::Resource]),
+            ReferenceType::FindInMap(f) => (&f.target, &[JumpDestinationType::Mapping]),
+            ReferenceType::If(i) => (&i.target, &[JumpDestinationType::Condition]),
+            ReferenceType::Condition(c) => (&c.target, &[JumpDestinationType::Condition]),
+            // variables declared in the variable map are not template symbols
+            ReferenceType::Sub(s) if s.local.is_some() => continue,
+            ReferenceType::Sub(s) if s.attribute.is_some() => {
+                (&s.target, &[JumpDestinationType::Resource])
+            }
+            ReferenceType::Sub(s) => (&s.target, REF_TARGETS),
+        };
+        occurrences.push(Occurrence {
+            name: target.clone(),
+            span: reference.span,
+            types,
+            is_declaration: false,
+        });
+    }
+
+    occurrences
+}
+
+/// Syntax nodes holding the text of a scalar, which is where symbol names are written
+const SCALARS: &[&str] = &[
+    "plain_scalar",
+    "single_quote_scalar",
+    "double_quote_scalar",
+    "block_scalar",
+    "string",
+];
+
+/// The occurrence under the cursor, if any
+///
+/// The cursor is first resolved to the scalar of the syntax tree under it, so that tags,
+/// punctuation and whitespace never resolve to a symbol. A cursor just past the end of a name
+/// still counts.
+pub(crate) fn occurrence_at<'o>(
+    document: &Document,
+    occurrences: &'o [Occurrence],
+    position: LspPosition,
+) -> Option<&'o Occurrence> {
+    let position = document.text_position(position);
+    let before = position
+        .col
+        .checked_sub(1)
+        .map(|col| Position { col, ..position });
+    let scalar = scalar_at(document, position).or_else(|| scalar_at(document, before?))?;
+    let scalar = Span {
+        start: scalar.start_position().into(),
+        end: scalar.end_position().into(),
+    };
+    occurrences.iter().find(|o| {
+        o.span.contains(position) && scalar.contains(o.span.start) && scalar.contains(o.span.end)
+    })
+}
+
+fn scalar_at(document: &Document, position: Position) -> Option<Node<'_>> {
+    let node = document.node_at(position)?;
+    // the innermost node may be part of the scalar, such as the contents of a JSON string
+    [Some(node), node.parent()]
+        .into_iter()
+        .flatten()
+        .find(|node| SCALARS.contains(&node.kind()))
+}
+
+/// The declaration an occurrence refers to, if it is declared in the template
+pub(crate) fn declaration<'d>(
+    document: &'d Document,
+    occurrence: &Occurrence,
+) -> Option<&'d JumpDestination> {
+    declarations(document, occurrence).next()
+}
+
+/// Every declaration an occurrence refers to, of which there is more than one if the name is
+/// declared twice
+pub(crate) fn declarations<'d>(
+    document: &'d Document,
+    occurrence: &Occurrence,
+) -> impl Iterator<Item = &'d JumpDestination> {
+    document
+        .jump_destinations
+        .iter()
+        .filter(move |destination| occurrence.refers_to(destination))
+}
+
+/// Every occurrence of the same symbol as the given one, including its declaration
+///
+/// Names that are not declared in the template, such as pseudo parameters, match every usage of
+/// the same name instead.
+pub(crate) fn related<'o>(
+    document: &Document,
+    occurrences: &'o [Occurrence],
+    occurrence: &Occurrence,
+) -> Vec<&'o Occurrence> {
+    match declaration(document, occurrence) {
+        Some(declaration) => occurrences_of(occurrences, declaration).collect(),
+        None => occurrences
+            .iter()
+            .filter(|other| {
+                other.name == occurrence.name
+                    && other.types.iter().any(|t| occurrence.types.contains(t))
+            })
+            .collect(),
+    }
+}
+
+/// The key inside `Mappings` that a literal `Fn::FindInMap` key under the cursor refers to
+pub(crate) fn mapping_key_at(document: &Document, position: Position) -> Option<&Spanned<String>> {
+    let contains = |literal: &Literal| literal.span.contains(position);
+    document
+        .jump_sources
+        .iter()
+        .find_map(|reference| match &reference.typ {
+            ReferenceType::FindInMap(f) => {
+                let mapping = document.template.mapping(&f.target)?;
+                let top_level_key = f.top_level_key.as_ref();
+                if let Some(key) = top_level_key.filter(|key| contains(key)) {
+                    return mapping.top_level_keys().find(|k| k.value == key.name);
+                }
+                let key = f.second_level_key.as_ref().filter(|key| contains(key))?;
+                mapping
+                    .second_level_keys(top_level_key.map(|key| key.name.as_str()))
+                    .find(|k| k.value == key.name)
+            }
+            _ => None,
+        })
+}
+
+/// All occurrences referring to the declaration, including the declaration itself
+pub(crate) fn occurrences_of<'o>(
+    occurrences: &'o [Occurrence],
+    declaration: &JumpDestination,
+) -> impl Iterator<Item = &'o Occurrence> {
+    occurrences
+        .iter()
+        .filter(move |occurrence| occurrence.refers_to(declaration))
+}
+
+/// Symbol types that share a namespace with the declared type, and so cannot share a name
+pub(crate) fn namespace(r#type: JumpDestinationType) -> &'static [JumpDestinationType] {
+    match r#type {
+        JumpDestinationType::Resource | JumpDestinationType::Parameter => REF_TARGETS,
+        other => declared_type(other),
+    }
+}
+
+fn declared_type(r#type: JumpDestinationType) -> &'static [JumpDestinationType] {
+    match r#type {
+        JumpDestinationType::Resource => &[JumpDestinationType::Resource],
+        JumpDestinationType::Parameter => &[JumpDestinationType::Parameter],
+        JumpDestinationType::Mapping => &[JumpDestinationType::Mapping],
+        JumpDestinationType::Output => &[JumpDestinationType::Output],
+        JumpDestinationType::Condition => &[JumpDestinationType::Condition],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::test_document;
+
+    #[test]
+    fn occurrences_of_a_resource() {
+        let doc = test_document("stack.yml", "yaml", include_str!("../testdata/rename.yml"));
+        let occurrences = occurrences(&doc);
+        let topic = doc
+            .jump_destinations
+            .iter()
+            .find(|d| d.name == "Topic")
+            .unwrap();
+        let mut lines: Vec<_> = occurrences_of(&occurrences, topic)
+            .map(|o| (o.span.start.line, o.is_declaration))
+            .collect();
+        lines.sort();
+        insta::assert_debug_snapshot!(lines);
+    }
+
+    /// Name and role of the symbol under the cursor
+    fn symbol_at(doc: &Document, line: u32, character: u32) -> Option<(String, bool)> {
+        let occurrences = occurrences(doc);
+        occurrence_at(doc, &occurrences, LspPosition { line, character })
+            .map(|o| (o.name.clone(), o.is_declaration))
+    }
+
+    #[test]
+    fn resolving_the_symbol_under_the_cursor() {
+        let doc = test_document(
+            "stack.yml",
+            "yaml",
+            "Resources:\n  Bucket:\n    Type: AWS::S3::Bucket\n  Policy:\n    \
+             Type: AWS::S3::BucketPolicy\n    Properties:\n      \
+             Bucket: !Ref \"Bucket\"\n      \
+             PolicyDocument:\n        \
+             Resource: [!GetAtt Bucket.Arn, !Sub \"${Bucket.Arn}/*\"]\n        \
+             Sid: !GetAtt [Bucket, Arn]\n",
+        );
+        let bucket = Some(("Bucket".to_string(), false));
+        // the declaration
+        assert_eq!(symbol_at(&doc, 1, 4), Some(("Bucket".to_string(), true)));
+        // quoted `!Ref "Bucket"`, including the cursor just past the name
+        assert_eq!(symbol_at(&doc, 6, 21), bucket);
+        assert_eq!(symbol_at(&doc, 6, 26), bucket);
+        // `!GetAtt Bucket.Arn` but not its attribute
+        assert_eq!(symbol_at(&doc, 8, 29), bucket);
+        assert_eq!(symbol_at(&doc, 8, 35), None);
+        // `${Bucket.Arn}` inside a `Sub` string, but not the text around it
+        assert_eq!(symbol_at(&doc, 8, 50), bucket);
+        assert_eq!(symbol_at(&doc, 8, 60), None);
+        // `[Bucket, Arn]`
+        assert_eq!(symbol_at(&doc, 9, 23), bucket);
+        assert_eq!(symbol_at(&doc, 9, 29), None);
+        // tags, keys that are not symbols and whitespace
+        assert_eq!(symbol_at(&doc, 6, 15), None);
+        assert_eq!(symbol_at(&doc, 6, 8), None);
+        assert_eq!(symbol_at(&doc, 5, 0), None);
+        // past the end of a line or the document
+        assert_eq!(symbol_at(&doc, 2, 80), None);
+        assert_eq!(symbol_at(&doc, 40, 0), None);
+    }
+
+    #[test]
+    fn names_declared_twice() {
+        let doc = test_document(
+            "stack.yml",
+            "yaml",
+            "Resources:\n  Topic:\n    Type: AWS::SNS::Topic\n  Topic:\n    \
+             Type: AWS::SNS::Topic\n  Queue:\n    Type: AWS::SQS::Queue\n    DependsOn: Topic\n",
+        );
+        let occurrences = occurrences(&doc);
+        let occurrence = occurrence_at(&doc, &occurrences, LspPosition::new(7, 17)).unwrap();
+        let lines: Vec<_> = declarations(&doc, occurrence)
+            .map(|declaration| declaration.span.start.line)
+            .collect();
+        assert_eq!(lines, [1, 3]);
+    }
+
+    #[test]
+    fn symbols_in_larger_templates() {
+        let doc = test_document(
+            "stack.yml",
+            "yaml",
+            include_str!("../testdata/template.yml"),
+        );
+        assert_eq!(
+            symbol_at(&doc, 46, 24),
+            Some(("TrustedAccounts".to_string(), false))
+        );
+
+        let doc = test_document(
+            "stack.json",
+            "json",
+            include_str!("../testdata/template.json"),
+        );
+        let bucket = "ServerlessDeploymentBucket".to_string();
+        assert_eq!(symbol_at(&doc, 4, 10), Some((bucket.clone(), true)));
+        assert_eq!(symbol_at(&doc, 22, 20), Some((bucket, false)));
+    }
+
+    #[test]
+    fn mapping_keys_of_findinmap() {
+        let doc = test_document(
+            "stack.yml",
+            "yaml",
+            include_str!("../testdata/findinmap.yml"),
+        );
+        let key_line = |line, col| {
+            mapping_key_at(&doc, Position { line, col })
+                .map(|key| (key.value.as_str(), key.span.start.line))
+        };
+
+        // !FindInMap [RegionMap, us-east-1, InstanceType]
+        assert_eq!(key_line(23, 44), Some(("us-east-1", 2)));
+        assert_eq!(key_line(23, 55), Some(("InstanceType", 4)));
+        // the top level key is `!Ref AWS::Region`, so the second level key could be in any
+        // top level key
+        assert_eq!(key_line(22, 12), Some(("AMI", 3)));
+        // the mapping name is not a key
+        assert_eq!(key_line(23, 32), None);
+    }
+}
@@ -0,0 +1,967 @@
+//! Typed model of a CloudFormation template
+//!
+//! The model is built from the tree-sitter syntax tree of either a YAML or JSON template, and
+//! every key and value carries the range it was parsed from. YAML short form intrinsic function
+//! tags (e.g. `!Ref Foo`) are normalised into their long form (`Ref: Foo`) so that consumers do
+//! not need to care which syntax the template was written in.
+use anyhow::Context;
+use tree_sitter::{Node, Parser, Tree};
+
+use crate::TemplateLanguage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub(crate) struct Position {
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+}
+
+impl From<tree_sitter::Point> for Position {
+    fn from(value: tree_sitter::Point) -> Self {
+        Self {
+            line: value.row,
+            col: value.column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub(crate) struct Span {
+    pub(crate) start: Position,
+    pub(crate) end: Position,
+}
+
+impl Span {
+    /// Whether the position lies within the span, including the position just past the end so
+    /// that a cursor placed after the last character still counts
+    pub(crate) fn contains(self, position: Position) -> bool {
+        self.start <= position && position <= self.end
+    }
+
+    fn from_node(node: &Node) -> Self {
+        Self {
+            start: node.start_position().into(),
+            end: node.end_position().into(),
+        }
+    }
+
+    /// Span of the contents of a quoted string, excluding the quotes
+    fn inside_quotes(node: &Node) -> Self {
+        let mut span = Self::from_node(node);
+        if span.start.line == span.end.line && span.end.col >= span.start.col + 2 {
+            span.start.col += 1;
+            span.end.col -= 1;
+        }
+        span
+    }
+}
+
+/// A value annotated with the source range it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Spanned<T> {
+    pub(crate) value: T,
+    pub(crate) span: Span,
+}
+
+/// Generic YAML/JSON value
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    /// Numbers are kept in their source representation
+    Number(String),
+    String(String),
+    Sequence(Vec<Spanned<Value>>),
+    /// Mappings keep their keys in document order, including duplicates
+    Mapping(Vec<(Spanned<String>, Spanned<Value>)>),
+}
+
+impl Value {
+    pub(crate) fn as_mapping(&self) -> Option<&[(Spanned<String>, Spanned<Value>)]> {
+        match self {
+            Value::Mapping(pairs) => Some(pairs),
+            _ => None,
+        }
+    }
+
+    /// The function name and argument if this value is an intrinsic function call, e.g.
+    /// `Fn::GetAtt: [Bucket, Arn]`
+    pub(crate) fn as_intrinsic(&self) -> Option<(&Spanned<String>, &Spanned<Value>)> {
+        match self.as_mapping()? {
+            [(key, argument)] if key.value == "Ref" || key.value.starts_with("Fn::") => {
+                Some((key, argument))
+            }
+            _ => None,
+        }
+    }
+
+    /// Look up the first value with the given key if this value is a mapping
+    pub(crate) fn get(&self, key: &str) -> Option<&Spanned<Value>> {
+        self.as_mapping()?
+            .iter()
+            .find(|(k, _)| k.value == key)
+            .map(|(_, v)| v)
+    }
+}
+
+/// Top level section of the template, e.g. `Resources`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Section<T> {
+    /// The section key, e.g. `Resources`
+    pub(crate) key: Spanned<String>,
+    /// Range covering the section key and all of its entries
+    pub(crate) span: Span,
+    pub(crate) entries: Vec<T>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Parameter {
+    pub(crate) name: Spanned<String>,
+    pub(crate) parameter_type: Option<Spanned<String>>,
+    pub(crate) description: Option<Spanned<String>>,
+    pub(crate) default: Option<Spanned<Value>>,
+    pub(crate) allowed_values: Option<Spanned<Value>>,
+    pub(crate) definition: Spanned<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mapping {
+    pub(crate) name: Spanned<String>,
+    pub(crate) definition: Spanned<Value>,
+}
+
+impl Mapping {
+    /// Keys of the first level of the mapping, e.g. the regions of a region map
+    pub(crate) fn top_level_keys(&self) -> impl Iterator<Item = &Spanned<String>> {
+        self.definition
+            .value
+            .as_mapping()
+            .unwrap_or_default()
+            .iter()
+            .map(|(key, _)| key)
+    }
+
+    /// Keys nested under the top level key, or under every top level key if it is not known
+    pub(crate) fn second_level_keys<'a>(
+        &'a self,
+        top_level_key: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a Spanned<String>> {
+        self.definition
+            .value
+            .as_mapping()
+            .unwrap_or_default()
+            .iter()
+            .filter(move |(key, _)| top_level_key.is_none_or(|top| key.value == top))
+            .flat_map(|(_, value)| value.value.as_mapping().unwrap_or_default())
+            .map(|(key, _)| key)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Condition {
+    pub(crate) name: Spanned<String>,
+    /// The condition expression
+    pub(crate) definition: Spanned<Value>,
+}
+
+/// An entry of the template `Metadata`, e.g. `AWS::CloudFormation::Interface`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Metadata {
+    pub(crate) name: Spanned<String>,
+    pub(crate) definition: Spanned<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rule {
+    pub(crate) name: Spanned<String>,
+    pub(crate) definition: Spanned<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Resource {
+    pub(crate) name: Spanned<String>,
+    pub(crate) resource_type: Option<Spanned<String>>,
+    pub(crate) properties: Option<Spanned<Value>>,
+    pub(crate) condition: Option<Spanned<String>>,
+    pub(crate) depends_on: Vec<Spanned<String>>,
+    pub(crate) definition: Spanned<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Output {
+    pub(crate) name: Spanned<String>,
+    pub(crate) description: Option<Spanned<String>>,
+    pub(crate) value: Option<Spanned<Value>>,
+    pub(crate) export_name: Option<Spanned<Value>>,
+    pub(crate) condition: Option<Spanned<String>>,
+    pub(crate) definition: Spanned<Value>,
+}
+
+/// Parsed CloudFormation template
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Template {
+    pub(crate) format_version: Option<Spanned<String>>,
+    pub(crate) description: Option<Spanned<String>>,
+    pub(crate) metadata: Option<Section<Metadata>>,
+    pub(crate) transform: Option<Spanned<Value>>,
+    pub(crate) parameters: Option<Section<Parameter>>,
+    pub(crate) mappings: Option<Section<Mapping>>,
+    pub(crate) conditions: Option<Section<Condition>>,
+    pub(crate) rules: Option<Section<Rule>>,
+    pub(crate) resources: Option<Section<Resource>>,
+    pub(crate) outputs: Option<Section<Output>>,
+}
+
+/// Parse the template text into a tree-sitter syntax tree with the grammar for the language
+#[cfg(test)]
+pub(crate) fn parse_tree(content: &str, language: TemplateLanguage) -> anyhow::Result<Tree> {
+    reparse_tree(content, language, None)
+}
+
+/// Parse the template text, reusing the unchanged parts of a previous tree that has already been
+/// updated with [`Tree::edit`]
+pub(crate) fn reparse_tree(
+    content: &str,
+    language: TemplateLanguage,
+    old_tree: Option<&Tree>,
+) -> anyhow::Result<Tree> {
+    let mut parser = Parser::new();
+    match language {
+        TemplateLanguage::Yaml => parser
+            .set_language(&tree_sitter_yaml::LANGUAGE.into())
+            .context("Error loading YAML grammar")?,
+        TemplateLanguage::Json => parser
+            .set_language(&tree_sitter_json::LANGUAGE.into())
+            .context("Error loading JSON grammar")?,
+    }
+    parser.parse(content, old_tree).context("parsing text")
+}
+
+impl Template {
+    #[cfg(test)]
+    pub(crate) fn parse(content: &str, language: TemplateLanguage) -> anyhow::Result<Self> {
+        let tree = parse_tree(content, language)?;
+        Ok(Self::from_tree(&tree, content, language))
+    }
+
+    /// Build the template model from an already parsed syntax tree
+    pub(crate) fn from_tree(tree: &Tree, content: &str, language: TemplateLanguage) -> Self {
+        let converter = Converter {
+            source: content.as_bytes(),
+        };
+        let root = match language {
+            TemplateLanguage::Yaml => converter.yaml_root(tree.root_node()),
+            TemplateLanguage::Json => converter.json_root(tree.root_node()),
+        };
+
+        let mut template = Template::default();
+        let Some(Spanned {
+            value: Value::Mapping(pairs),
+            ..
+        }) = root
+        else {
+            return template;
+        };
+
+        for (key, value) in pairs {
+            let section_span = Span {
+                start: key.span.start,
+                end: value.span.end,
+            };
+            match key.value.as_str() {
+                "AWSTemplateFormatVersion" => template.format_version = string_value(&value),
+                "Description" => template.description = string_value(&value),
+                "Metadata" => {
+                    template.metadata =
+                        Some(section(key, section_span, &value, |name, v| Metadata {
+                            name,
+                            definition: v.clone(),
+                        }))
+                }
+                "Transform" => template.transform = Some(value),
+                "Parameters" => {
+                    template.parameters = Some(section(key, section_span, &value, parameter))
+                }
+                "Mappings" => {
+                    template.mappings =
+                        Some(section(key, section_span, &value, |name, v| Mapping {
+                            name,
+                            definition: v.clone(),
+                        }))
+                }
+                "Conditions" => {
+                    template.conditions =
+                        Some(section(key, section_span, &value, |name, v| Condition {
+                            name,
+                            definition: v.clone(),
+                        }))
+                }
+                "Rules" => {
+                    template.rules = Some(section(key, section_span, &value, |name, v| Rule {
+                        name,
+                        definition: v.clone(),
+                    }))
+                }
+                "Resources" => {
+                    template.resources = Some(section(key, section_span, &value, resource))
+                }
+                "Outputs" => template.outputs = Some(section(key, section_span, &value, output)),
+                other => tracing::debug!(section = %other, "unknown top level template section"),
+            }
+        }
+
+        template
+    }
+
+    /// Look up a mapping by name
+    pub(crate) fn mapping(&self, name: &str) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .flat_map(|section| &section.entries)
+            .find(|mapping| mapping.name.value == name)
+    }
+
+    /// Find the resource whose `Type` value lies under the position
+    pub(crate) fn resource_type_at(&self, position: Position) -> Option<&str> {
+        self.resources
+            .iter()
+            .flat_map(|section| &section.entries)
+            .filter_map(|resource| resource.resource_type.as_ref())
+            .find(|resource_type| {
+                // the end point is exclusive here, so the cursor must be on a character
+                resource_type.span.start <= position && position < resource_type.span.end
+            })
+            .map(|resource_type| resource_type.value.as_str())
+    }
+}
+
+fn string_value(value: &Spanned<Value>) -> Option<Spanned<String>> {
+    let s = match &value.value {
+        Value::String(s) | Value::Number(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    Some(Spanned {
+        value: s,
+        span: value.span,
+    })
+}
+
+fn section<T>(
+    key: Spanned<String>,
+    span: Span,
+    value: &Spanned<Value>,
+    build: impl Fn(Spanned<String>, &Spanned<Value>) -> T,
+) -> Section<T> {
+    let entries = value
+        .value
+        .as_mapping()
+        .unwrap_or_default()
+        .iter()
+        .map(|(name, definition)| build(name.clone(), definition))
+        .collect();
+    Section { key, span, entries }
+}
+
+fn parameter(name: Spanned<String>, definition: &Spanned<Value>) -> Parameter {
+    let field = |key| definition.value.get(key);
+    Parameter {
+        name,
+        parameter_type: field("Type").and_then(string_value),
+        description: field("Description").and_then(string_value),
+        default: field("Default").cloned(),
+        allowed_values: field("AllowedValues").cloned(),
+        definition: definition.clone(),
+    }
+}
+
+fn resource(name: Spanned<String>, definition: &Spanned<Value>) -> Resource {
+    let field = |key| definition.value.get(key);
+    let depends_on = match field("DependsOn") {
+        Some(Spanned {
+            value: Value::Sequence(items),
+            ..
+        }) => items.iter().filter_map(string_value).collect(),
+        Some(value) => string_value(value).into_iter().collect(),
+        None => Vec::new(),
+    };
+    Resource {
+        name,
+        resource_type: field("Type").and_then(string_value),
+        properties: field("Properties").cloned(),
+        condition: field("Condition").and_then(string_value),
+        depends_on,
+        definition: definition.clone(),
+    }
+}
+
+fn output(name: Spanned<String>, definition: &Spanned<Value>) -> Output {
+    let field = |key| definition.value.get(key);
+    Output {
+        name,
+        description: field("Description").and_then(string_value),
+        value: field("Value").cloned(),
+        export_name: field("Export").and_then(|export| export.value.get("Name").cloned()),
+        condition: field("Condition").and_then(string_value),
+        definition: definition.clone(),
+    }
+}
+
+/// Convert a YAML short form tag into the long form intrinsic function name
+fn intrinsic_for_tag(tag: &str) -> Option<String> {
+    let name = tag.strip_prefix('!')?;
+    match name {
+        "Ref" | "Condition" => Some(name.to_string()),
+        "Base64" | "Cidr" | "FindInMap" | "ForEach" | "GetAtt" | "GetAZs" | "ImportValue"
+        | "Join" | "Length" | "Select" | "Split" | "Sub" | "ToJsonString" | "Transform" | "And"
+        | "Equals" | "If" | "Not" | "Or" => Some(format!("Fn::{name}")),
+        _ if name.starts_with("Fn::") => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Conversion from the tree-sitter syntax tree into [`Value`]s
+struct Converter<'s> {
+    source: &'s [u8],
+}
+
+impl Converter<'_> {
+    fn text(&self, node: &Node) -> &str {
+        node.utf8_text(self.source).unwrap_or_default()
+    }
+
+    fn yaml_root(&self, root: Node) -> Option<Spanned<Value>> {
+        // stream -> document -> node; only the first document of a stream is a template
+        let mut cursor = root.walk();
+        let document = root
+            .named_children(&mut cursor)
+            .find(|n| n.kind() == "document")?;
+        let mut cursor = document.walk();
+        let node = document
+            .named_children(&mut cursor)
+            .find(|n| matches!(n.kind(), "block_node" | "flow_node"))?;
+        self.yaml_node(node)
+    }
+
+    fn yaml_node(&self, node: Node) -> Option<Spanned<Value>> {
+        let span = Span::from_node(&node);
+        match node.kind() {
+            "block_node" | "flow_node" => {
+                let mut tag = None;
+                let mut inner = None;
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    match child.kind() {
+                        "tag" => tag = Some(child),
+                        "anchor" | "comment" => {}
+                        _ => inner = self.yaml_node(child),
+                    }
+                }
+
+                let Some(tag) = tag else {
+                    return inner;
+                };
+                let Some(function) = intrinsic_for_tag(self.text(&tag)) else {
+                    return inner;
+                };
+                // a tag without a value, e.g. while the user is still typing `!Ref `
+                let argument = inner.unwrap_or(Spanned {
+                    value: Value::Null,
+                    span: Span {
+                        start: span.end,
+                        end: span.end,
+                    },
+                });
+                Some(Spanned {
+                    value: Value::Mapping(vec![(
+                        Spanned {
+                            value: function,
+                            span: Span::from_node(&tag),
+                        },
+                        argument,
+                    )]),
+                    span,
+                })
+            }
+            "block_mapping" | "flow_mapping" => {
+                let mut pairs = Vec::new();
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    match child.kind() {
+                        "block_mapping_pair" | "flow_pair" => {
+                            pairs.extend(self.yaml_pair(child));
+                        }
+                        // `{ Key }` is a key with a null value
+                        "flow_node" => {
+                            if let Some(key) = self.yaml_key(child) {
+                                let value = Spanned {
+                                    value: Value::Null,
+                                    span: key.span,
+                                };
+                                pairs.push((key, value));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Some(Spanned {
+                    value: Value::Mapping(pairs),
+                    span,
+                })
+            }
+            "block_sequence" => {
+                let mut items = Vec::new();
+                let mut cursor = node.walk();
+                for item in node.named_children(&mut cursor) {
+                    if item.kind() != "block_sequence_item" {
+                        continue;
+                    }
+                    let mut item_cursor = item.walk();
+                    let value = item
+                        .named_children(&mut item_cursor)
+                        .filter(|n| n.kind() != "comment")
+                        .find_map(|n| self.yaml_node(n));
+                    items.push(value.unwrap_or(Spanned {
+                        value: Value::Null,
+                        span: Span::from_node(&item),
+                    }));
+                }
+                Some(Spanned {
+                    value: Value::Sequence(items),
+                    span,
+                })
+            }
+            "flow_sequence" => {
+                let mut items = Vec::new();
+                let mut cursor = node.walk();
+                for child in node.named_children(&mut cursor) {
+                    match child.kind() {
+                        "flow_node" => items.extend(self.yaml_node(child)),
+                        // `[Key: Value]` is a single pair mapping
+                        "flow_pair" => {
+                            if let Some(pair) = self.yaml_pair(child) {
+                                items.push(Spanned {
+                                    value: Value::Mapping(vec![pair]),
+                                    span: Span::from_node(&child),
+                                });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Some(Spanned {
+                    value: Value::Sequence(items),
+                    span,
+                })
+            }
+            "plain_scalar" => {
+                let text = self.text(&node);
+                let value = match node.named_child(0).map(|n| n.kind()) {
+                    Some("null_scalar") => Value::Null,
+                    Some("boolean_scalar") => Value::Bool(matches!(text, "true" | "True" | "TRUE")),
+                    Some("integer_scalar" | "float_scalar") => Value::Number(text.to_string()),
+                    _ => Value::String(text.to_string()),
+                };
+                Some(Spanned { value, span })
+            }
+            "double_quote_scalar" => {
+                let text = self.text(&node);
+                let value = serde_json::from_str::<String>(text)
+                    .unwrap_or_else(|_| text.trim_matches('"').to_string());
+                Some(Spanned {
+                    value: Value::String(value),
+                    span: Span::inside_quotes(&node),
+                })
+            }
+            "single_quote_scalar" => {
+                let text = self.text(&node);
+                let inner = text
+                    .strip_prefix('\'')
+                    .and_then(|t| t.strip_suffix('\''))
+                    .unwrap_or(text);
+                Some(Spanned {
+                    value: Value::String(inner.replace("''", "'")),
+                    span: Span::inside_quotes(&node),
+                })
+            }
+            "block_scalar" => Some(Spanned {
+                value: Value::String(block_scalar_value(self.text(&node))),
+                span,
+            }),
+            _ => None,
+        }
+    }
+
+    fn yaml_key(&self, node: Node) -> Option<Spanned<String>> {
+        let value = self.yaml_node(node)?;
+        string_value(&value)
+    }
+
+    fn yaml_pair(&self, pair: Node) -> Option<(Spanned<String>, Spanned<Value>)> {
+        let key = self.yaml_key(pair.child_by_field_name("key")?)?;
+        let value = pair
+            .child_by_field_name("value")
+            .and_then(|v| self.yaml_node(v))
+            .unwrap_or(Spanned {
+                value: Value::Null,
+                span: Span {
+                    start: pair.end_position().into(),
+                    end: pair.end_position().into(),
+                },
+            });
+        Some((key, value))
+    }
+
+    fn json_root(&self, root: Node) -> Option<Spanned<Value>> {
+        let mut cursor = root.walk();
+        let node = root
+            .named_children(&mut cursor)
+            .find(|n| n.kind() != "comment")?;
+        self.json_node(node)
+    }
+
+    fn json_node(&self, node: Node) -> Option<Spanned<Value>> {
+        let mut span = Span::from_node(&node);
+        let value = match node.kind() {
+            "object" => {
+                let mut pairs = Vec::new();
+                let mut cursor = node.walk();
+                for pair in node.named_children(&mut cursor) {
+                    if pair.kind() != "pair" {
+                        continue;
+                    }
+                    let Some(key) = pair
+                        .child_by_field_name("key")
+                        .and_then(|k| self.json_node(k))
+                        .as_ref()
+                        .and_then(string_value)
+                    else {
+                        continue;
+                    };
+                    let value = pair
+                        .child_by_field_name("value")
+                        .and_then(|v| self.json_node(v))
+                        .unwrap_or(Spanned {
+                            value: Value::Null,
+                            span: Span {
+                                start: pair.end_position().into(),
+                                end: pair.end_position().into(),
+                            },
+                        });
+                    pairs.push((key, value));
+                }
+                Value::Mapping(pairs)
+            }
+            "array" => {
+                let mut cursor = node.walk();
+                Value::Sequence(
+                    node.named_children(&mut cursor)
+                        .filter_map(|n| self.json_node(n))
+                        .collect(),
+                )
+            }
+            "string" => {
+                let text = self.text(&node);
+                span = Span::inside_quotes(&node);
+                Value::String(
+                    serde_json::from_str::<String>(text)
+                        .unwrap_or_else(|_| text.trim_matches('"').to_string()),
+                )
+            }
+            "number" => Value::Number(self.text(&node).to_string()),
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "null" => Value::Null,
+            _ => return None,
+        };
+        Some(Spanned { value, span })
+    }
+}
+
+/// Compute the contents of a literal (`|`) or folded (`>`) block scalar from its source text
+fn block_scalar_value(text: &str) -> String {
+    let mut lines = text.lines();
+    let header = lines.next().unwrap_or_default().trim();
+    let folded = header.starts_with('>');
+    let chomping = header.chars().find(|c| matches!(c, '-' | '+'));
+    let explicit_indent = header
+        .chars()
+        .find_map(|c| c.to_digit(10))
+        .map(|d| d as usize);
+
+    let lines: Vec<&str> = lines.collect();
+    let indent = explicit_indent.unwrap_or_else(|| {
+        lines
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0)
+    });
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or_default())
+        .collect();
+
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            let previous = lines[i - 1];
+            if folded && !line.is_empty() && !previous.is_empty() && !line.starts_with(' ') {
+                out.push(' ');
+            } else if folded && line.is_empty() && !previous.is_empty() {
+                // the line break before a run of empty lines is folded away
+            } else {
+                out.push('\n');
+            }
+        }
+        out.push_str(line);
+    }
+
+    match chomping {
+        Some('-') => out.trim_end_matches('\n').to_string(),
+        Some('+') => out + "\n",
+        _ => {
+            let mut out = out.trim_end_matches('\n').to_string();
+            out.push('\n');
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names<T>(section: &Option<Section<T>>, name: impl Fn(&T) -> &str) -> Vec<String> {
+        section
+            .iter()
+            .flat_map(|s| &s.entries)
+            .map(|e| name(e).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_yaml_template() {
+        let contents = include_str!("../testdata/template.yml");
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+
+        assert_eq!(
+            template.description.as_ref().map(|d| d.value.as_str()),
+            Some(
+                "This stack includes resources needed to deploy AWS CDK apps into this environment"
+            )
+        );
+        assert_eq!(
+            names(&template.parameters, |p| &p.name.value)[..3],
+            [
+                "TrustedAccounts",
+                "TrustedAccountsForLookup",
+                "CloudFormationExecutionPolicies"
+            ]
+        );
+        let parameter = &template.parameters.as_ref().unwrap().entries[0];
+        assert_eq!(
+            parameter.parameter_type.as_ref().unwrap().value,
+            "CommaDelimitedList"
+        );
+        assert_eq!(
+            parameter.name.span,
+            Span {
+                start: Position { line: 2, col: 2 },
+                end: Position { line: 2, col: 17 },
+            }
+        );
+        assert!(!names(&template.conditions, |c| &c.name.value).is_empty());
+        assert!(!names(&template.outputs, |o| &o.name.value).is_empty());
+    }
+
+    #[test]
+    fn parse_json_template() {
+        let contents = include_str!("../testdata/template.json");
+        let template = Template::parse(contents, TemplateLanguage::Json).unwrap();
+
+        assert_eq!(
+            template.format_version.as_ref().map(|v| v.value.as_str()),
+            Some("2010-09-09")
+        );
+        let resources = template.resources.as_ref().unwrap();
+        assert_eq!(resources.entries.len(), 11);
+        let function = resources
+            .entries
+            .iter()
+            .find(|r| r.name.value == "ApiLambdaFunction")
+            .unwrap();
+        assert_eq!(
+            function.resource_type.as_ref().unwrap().value,
+            "AWS::Lambda::Function"
+        );
+        assert_eq!(
+            function
+                .depends_on
+                .iter()
+                .map(|d| d.value.as_str())
+                .collect::<Vec<_>>(),
+            ["ApiLogGroup"]
+        );
+        // spans exclude the quotes of the JSON string
+        assert_eq!(
+            function.name.span,
+            Span {
+                start: Position { line: 157, col: 5 },
+                end: Position { line: 157, col: 22 },
+            }
+        );
+        assert_eq!(
+            names(&template.outputs, |o| &o.name.value),
+            ["ServerlessDeploymentBucketName", "ServiceEndpoint"]
+        );
+    }
+
+    #[test]
+    fn short_form_tags_are_normalised() {
+        let contents = include_str!("../testdata/getatt.yml");
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+        let function = &template.resources.as_ref().unwrap().entries[3];
+        let properties = &function.properties.as_ref().unwrap().value;
+        let bucket_arn = properties
+            .get("Environment")
+            .and_then(|e| e.value.get("Variables"))
+            .and_then(|v| v.value.get("BUCKET_ARN"))
+            .unwrap();
+
+        let [(function, argument)] = bucket_arn.value.as_mapping().unwrap() else {
+            panic!("expected a single intrinsic function");
+        };
+        assert_eq!(function.value, "Fn::GetAtt");
+        assert_eq!(argument.value, Value::String("MyBucket.Arn".to_string()));
+    }
+
+    #[test]
+    fn yaml_and_json_are_equivalent() {
+        let yaml = r#"
+Resources:
+  Topic:
+    Type: AWS::SNS::Topic
+    Properties:
+      DisplayName: !Ref Name
+      Tags:
+        - Key: a
+          Value: 'b'
+"#;
+        let json = r#"{
+  "Resources": {
+    "Topic": {
+      "Type": "AWS::SNS::Topic",
+      "Properties": {
+        "DisplayName": {"Ref": "Name"},
+        "Tags": [{"Key": "a", "Value": "b"}]
+      }
+    }
+  }
+}"#;
+        let strip = |value: &Value| -> serde_json::Value {
+            fn strip(value: &Value) -> serde_json::Value {
+                match value {
+                    Value::Null => serde_json::Value::Null,
+                    Value::Bool(b) => serde_json::Value::Bool(*b),
+                    Value::Number(n) => serde_json::Value::String(n.clone()),
+                    Value::String(s) => serde_json::Value::String(s.clone()),
+                    Value::Sequence(items) => items.iter().map(|i| strip(&i.value)).collect(),
+                    Value::Mapping(pairs) => pairs
+                        .iter()
+                        .map(|(k, v)| (k.value.clone(), strip(&v.value)))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into(),
+                }
+            }
+            strip(value)
+        };
+
+        let yaml = Template::parse(yaml, TemplateLanguage::Yaml).unwrap();
+        let json = Template::parse(json, TemplateLanguage::Json).unwrap();
+        let resource = |t: &Template| t.resources.as_ref().unwrap().entries[0].clone();
+        assert_eq!(
+            strip(&resource(&yaml).definition.value),
+            strip(&resource(&json).definition.value)
+        );
+    }
+
+    #[test]
+    fn block_scalars() {
+        assert_eq!(block_scalar_value("|\n  a\n  b"), "a\nb\n");
+        assert_eq!(block_scalar_value("|-\n  a\n  b"), "a\nb");
+        assert_eq!(block_scalar_value(">\n  a\n  b\n\n  c"), "a b\nc\n");
+    }
+
+    #[test]
+    fn mapping_keys() {
+        let contents = include_str!("../testdata/findinmap.yml");
+        let template = Template::parse(contents, TemplateLanguage::Yaml).unwrap();
+        let mapping = template.mapping("RegionMap").unwrap();
+        let keys = |keys: Vec<&Spanned<String>>| -> Vec<String> {
+            keys.into_iter().map(|key| key.value.clone()).collect()
+        };
+
+        assert_eq!(
+            keys(mapping.top_level_keys().collect()),
+            ["us-east-1", "us-west-2"]
+        );
+        assert_eq!(
+            keys(mapping.second_level_keys(Some("us-west-2")).collect()),
+            ["AMI", "InstanceType"]
+        );
+        assert_eq!(keys(mapping.second_level_keys(None).collect()).len(), 4);
+        assert!(
+            mapping
+                .second_level_keys(Some("eu-west-1"))
+                .next()
+                .is_none()
+        );
+        assert!(template.mapping("Missing").is_none());
+    }
+
+    // tests for looking up the resource type under the cursor
+    fn resource_type_at(
+        contents: &str,
+        language: TemplateLanguage,
+        line: usize,
+        col: usize,
+    ) -> Option<String> {
+        let template = Template::parse(contents, language).unwrap();
+        template
+            .resource_type_at(Position { line, col })
+            .map(ToString::to_string)
+    }
+
+    #[test]
+    fn extract_simple() {
+        let contents = "Resources:\n  Topic:\n    Type: AWS::SNS::Topic";
+        assert_eq!(
+            resource_type_at(contents, TemplateLanguage::Yaml, 2, 12),
+            Some("AWS::SNS::Topic".to_string())
+        );
+    }
+
+    #[test]
+    fn extract_not_over() {
+        let contents = "Resources:\n  Topic:\n    Type: AWS::SNS::Topic";
+        assert!(resource_type_at(contents, TemplateLanguage::Yaml, 2, 4).is_none());
+    }
+
+    #[test]
+    fn extract_past_end() {
+        let contents = "Resources:\n  Topic:\n    Type: AWS::SNS::Topic     ";
+        assert!(resource_type_at(contents, TemplateLanguage::Yaml, 2, 28).is_none());
+    }
+
+    #[test]
+    fn extract_from_json() {
+        let contents = r#"{"Resources": {"Topic": {"Type": "AWS::SNS::Topic"}}}"#;
+        assert_eq!(
+            resource_type_at(contents, TemplateLanguage::Json, 0, 36),
+            Some("AWS::SNS::Topic".to_string())
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Diagnostics computed by validating resource properties against the resource provider schemas
+use cfn_lsp_schema::{PrimitiveType, PropertySchema, ResourceSchema};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity};
+
+use crate::{
+    diagnostics::DIAGNOSTIC_SOURCE,
+    document::lsp_range,
+    template::{Span, Spanned, Template, Value},
+};
+
+/// Validate the `Properties` of every resource whose type has a schema, in the text of `rope`
+pub(crate) fn schema_violations(rope: &Rope, template: &Template) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        rope,
+        diagnostics: Vec::new(),
+    };
+    for resource in template.resources.iter().flat_map(|s| &s.entries) {
+        let Some(resource_type) = &resource.resource_type else {
+            continue;
+        };
+        // custom resources accept any properties
+        if resource_type.value.starts_with("Custom::") {
+            continue;
+        }
+        let schema = match cfn_lsp_schema::resource_schema(&resource_type.value) {
+            Ok(schema) => schema,
+            Err(e) => {
+                tracing::debug!(error = %e, resource_type = %resource_type.value, "no schema for resource type");
+                continue;
+            }
+        };
+
+        match &resource.properties {
+            Some(properties) if properties.value.as_intrinsic().is_none() => {
+                validator.validate(&schema, &schema.root, properties, resource.name.span);
+            }
+            Some(_) => {}
+            None => validator.missing_required(&schema, &schema.root, &[], resource.name.span),
+        }
+    }
+    validator.diagnostics
+}
+
+struct Validator<'r> {
+    rope: &'r Rope,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, span: Span, severity: DiagnosticSeverity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            range: lsp_range(self.rope, span),
+            severity: Some(severity),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message,
+            ..Default::default()
+        });
+    }
+
+    /// Validate a value against a schema
+    ///
+    /// `key_span` is the span of the key the value belongs to, which is where missing
+    /// properties are reported.
+    fn validate(
+        &mut self,
+        resource: &ResourceSchema,
+        schema: &PropertySchema,
+        value: &Spanned<Value>,
+        key_span: Span,
+    ) {
+        // the result of an intrinsic function is not known until the stack is deployed
+        if value.value.as_intrinsic().is_some() {
+            return;
+        }
+        let schema = resource.resolve(schema);
+
+        match &value.value {
+            // an empty value, usually while the template is being edited
+            Value::Null => {}
+            Value::Mapping(pairs) => {
+                if !self.check_type(schema, PrimitiveType::Object, value.span) {
+                    return;
+                }
+                for (key, property_value) in pairs {
+                    match resource.property(schema, &key.value) {
+                        Some(property) => {
+                            self.validate(resource, property, property_value, key.span)
+                        }
+                        None if schema.additional_properties == Some(false) => self.report(
+                            key.span,
+                            DiagnosticSeverity::ERROR,
+                            format!("Unknown property `{}`", key.value),
+                        ),
+                        None => {}
+                    }
+                }
+                let present: Vec<&str> = pairs.iter().map(|(k, _)| k.value.as_str()).collect();
+                self.missing_required(resource, schema, &present, key_span);
+            }
+            Value::Sequence(items) => {
+                if !self.check_type(schema, PrimitiveType::Array, value.span) {
+                    return;
+                }
+                let count = items.len() as u64;
+                if let Some(min) = schema.min_items.filter(|min| count < *min) {
+                    self.report(
+                        value.span,
+                        DiagnosticSeverity::ERROR,
+                        format!("Expected at least {min} items, found {count}"),
+                    );
+                }
+                if let Some(max) = schema.max_items.filter(|max| count > *max) {
+                    self.report(
+                        value.span,
+                        DiagnosticSeverity::ERROR,
+                        format!("Expected at most {max} items, found {count}"),
+                    );
+                }
+                if let Some(item_schema) = resource.items(schema) {
+                    for item in items {
+                        self.validate(resource, item_schema, item, item.span);
+                    }
+                }
+            }
+            Value::String(s) | Value::Number(s) => self.validate_scalar(resource, schema, s, value),
+            Value::Bool(b) => self.validate_scalar(resource, schema, &b.to_string(), value),
+        }
+    }
+
+    /// Report a type mismatch if the schema does not accept the given type
+    fn check_type(&mut self, schema: &PropertySchema, found: PrimitiveType, span: Span) -> bool {
+        if schema.types.is_empty() || schema.types.contains(&found) {
+            return true;
+        }
+        self.report(
+            span,
+            DiagnosticSeverity::ERROR,
+            format!("Expected {}, found {found}", describe_types(&schema.types)),
+        );
+        false
+    }
+
+    fn missing_required(
+        &mut self,
+        resource: &ResourceSchema,
+        schema: &PropertySchema,
+        present: &[&str],
+        span: Span,
+    ) {
+        let schema = resource.resolve(schema);
+        let missing: Vec<_> = schema
+            .required
+            .iter()
+            .filter(|name| !present.contains(&name.as_str()))
+            .map(|name| format!("`{name}`"))
+            .collect();
+        if !missing.is_empty() {
+            self.report(
+                span,
+                DiagnosticSeverity::ERROR,
+                format!("Missing required properties: {}", missing.join(", ")),
+            );
+        }
+    }
+
+    /// Validate a scalar value, given in its source representation
+    ///
+    /// CloudFormation converts scalars between strings, numbers and booleans, so a scalar is only
+    /// a type error if it cannot be converted to any of the accepted types.
+    fn validate_scalar(
+        &mut self,
+        resource: &ResourceSchema,
+        schema: &PropertySchema,
+        text: &str,
+        value: &Spanned<Value>,
+    ) {
+        let converts_to = |t: &PrimitiveType| match t {
+            PrimitiveType::String => true,
+            PrimitiveType::Integer => text.parse::<i64>().is_ok(),
+            PrimitiveType::Number => text.parse::<f64>().is_ok(),
+            PrimitiveType::Boolean => matches!(text, "true" | "false"),
+            PrimitiveType::Object | PrimitiveType::Array | PrimitiveType::Null => false,
+        };
+        if !schema.types.is_empty() && !schema.types.iter().any(converts_to) {
+            let found = match value.value {
+                Value::Number(_) => PrimitiveType::Number,
+                Value::Bool(_) => PrimitiveType::Boolean,
+                _ => PrimitiveType::String,
+            };
+            self.report(
+                value.span,
+                DiagnosticSeverity::ERROR,
+                format!("Expected {}, found {found}", describe_types(&schema.types)),
+            );
+            return;
+        }
+
+        if let Some(allowed) = &schema.enum_values {
+            let matches = allowed.iter().any(|v| match v {
+                serde_json::Value::String(s) => s == text,
+                serde_json::Value::Number(n) => text.parse::<f64>().ok() == n.as_f64(),
+                serde_json::Value::Bool(b) => text == b.to_string(),
+                _ => false,
+            });
+            if !matches {
+                let allowed: Vec<_> = allowed
+                    .iter()
+                    .map(|v| match v {
+                        serde_json::Value::String(s) => format!("`{s}`"),
+                        other => format!("`{other}`"),
+                    })
+                    .collect();
+                self.report(
+                    value.span,
+                    DiagnosticSeverity::ERROR,
+                    format!("`{text}` is not one of {}", allowed.join(", ")),
+                );
+                return;
+            }
+        }
+
+        let is_string = schema.types.is_empty() || schema.types.contains(&PrimitiveType::String);
+        if is_string {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.min_length.filter(|min| length < *min) {
+                self.report(
+                    value.span,
+                    DiagnosticSeverity::ERROR,
+                    format!("Expected at least {min} characters, found {length}"),
+                );
+            }
+            if let Some(max) = schema.max_length.filter(|max| length > *max) {
+                self.report(
+                    value.span,
+                    DiagnosticSeverity::ERROR,
+                    format!("Expected at most {max} characters, found {length}"),
+                );
+            }
+            // schema patterns are ECMAScript regular expressions, so only report the mismatch as
+            // a warning
+            if let Some(pattern) = &schema.pattern
+                && resource.matches_pattern(schema, text) == Some(false)
+            {
+                self.report(
+                    value.span,
+                    DiagnosticSeverity::WARNING,
+                    format!("`{text}` does not match the pattern `{pattern}`"),
+                );
+            }
+        }
+
+        if let Ok(number) = text.parse::<f64>() {
+            if let Some(min) = schema.minimum.filter(|min| number < *min) {
+                self.report(
+                    value.span,
+                    DiagnosticSeverity::ERROR,
+                    format!("Expected a value of at least {min}, found {text}"),
+                );
+            }
+            if let Some(max) = schema.maximum.filter(|max| number > *max) {
+                self.report(
+                    value.span,
+                    DiagnosticSeverity::ERROR,
+                    format!("Expected a value of at most {max}, found {text}"),
+                );
+            }
+        }
+    }
+}
+
+fn describe_types(types: &[PrimitiveType]) -> String {
+    types
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TemplateLanguage;
+
+    fn violations(contents: &str, language: TemplateLanguage) -> Vec<Diagnostic> {
+        let template = Template::parse(contents, language).unwrap();
+        schema_violations(&Rope::from_str(contents), &template)
+    }
+
+    #[test]
+    fn valid_templates() {
+        for contents in [
+            include_str!("../testdata/two_resources.yml"),
+            include_str!("../testdata/subs.yml"),
+            include_str!("../testdata/sections.yml"),
+            include_str!("../testdata/outputs.yml"),
+            include_str!("../testdata/if.yml"),
+            include_str!("../testdata/findinmap.yml"),
+            include_str!("../testdata/dependson.yml"),
+            include_str!("../testdata/sub_syntaxes.yml"),
+        ] {
+            assert_eq!(violations(contents, TemplateLanguage::Yaml), Vec::new());
+        }
+        for contents in [
+            include_str!("../testdata/template.json"),
+            include_str!("../testdata/sections.json"),
+            include_str!("../testdata/sub_syntaxes.json"),
+        ] {
+            assert_eq!(violations(contents, TemplateLanguage::Json), Vec::new());
+        }
+    }
+
+    #[test]
+    fn invalid_properties() {
+        let contents = include_str!("../testdata/invalid_properties.yml");
+        insta::assert_yaml_snapshot!(violations(contents, TemplateLanguage::Yaml));
+    }
+}
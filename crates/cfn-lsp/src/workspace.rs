@@ -0,0 +1,426 @@
+//! Index of the symbols declared by every template in the workspace, for `workspace/symbol`
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{Location, Range, SymbolInformation, SymbolKind, Url};
+
+use crate::{
+    detect_template_language,
+    document::lsp_range,
+    template::{Span, Template, Value, reparse_tree},
+};
+
+/// Extensions of the files that may hold a template
+const TEMPLATE_EXTENSIONS: &[&str] = &["yml", "yaml", "json", "template"];
+
+/// Glob matching the files that may hold a template, for the file watcher
+pub(crate) const TEMPLATE_GLOB: &str = "**/*.{yml,yaml,json,template}";
+
+/// Directories that hold dependencies or build output rather than templates
+const SKIPPED_DIRECTORIES: &[&str] = &["node_modules", "target"];
+
+/// A symbol declared by a template, which can be searched for by name
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct IndexedSymbol {
+    pub(crate) name: String,
+    pub(crate) kind: SymbolKind,
+    /// The section declaring the symbol, e.g. `Resources`
+    pub(crate) container: &'static str,
+    pub(crate) range: Range,
+}
+
+/// Symbols of the templates found on disk, keyed by the URI of their file
+#[derive(Debug, Default)]
+pub(crate) struct WorkspaceIndex {
+    files: HashMap<Url, Vec<IndexedSymbol>>,
+    /// Files updated since the last merge, which are newer than what a walk of the workspace
+    /// running in the meantime found
+    updated: HashSet<Url>,
+    /// Sequence number of the last change applied to each file
+    sequences: HashMap<Url, u64>,
+    last_sequence: u64,
+}
+
+impl WorkspaceIndex {
+    /// Number a batch of file changes before reading the files
+    ///
+    /// Batches are read concurrently, so a later change to a file can be read first; passing the
+    /// number to [`WorkspaceIndex::update`] stops the earlier contents overwriting it.
+    pub(crate) fn next_sequence(&mut self) -> u64 {
+        self.last_sequence += 1;
+        self.last_sequence
+    }
+
+    /// Replace the symbols of a file, or drop the file if it is no longer a template
+    ///
+    /// Nothing changes if a later change to the file has already been applied.
+    pub(crate) fn update(&mut self, uri: Url, symbols: Option<Vec<IndexedSymbol>>, sequence: u64) {
+        if self
+            .sequences
+            .get(&uri)
+            .is_some_and(|applied| *applied > sequence)
+        {
+            tracing::trace!(%uri, sequence, "skipping an outdated change");
+            return;
+        }
+        self.sequences.insert(uri.clone(), sequence);
+        self.updated.insert(uri.clone());
+        match symbols {
+            Some(symbols) => {
+                self.files.insert(uri, symbols);
+            }
+            None => {
+                self.files.remove(&uri);
+            }
+        }
+    }
+
+    /// Add the files found by a walk of the workspace, keeping those updated while it ran
+    pub(crate) fn merge(&mut self, walked: WorkspaceIndex) {
+        for (uri, symbols) in walked.files {
+            if !self.updated.contains(&uri) {
+                self.files.insert(uri, symbols);
+            }
+        }
+        self.updated.clear();
+    }
+
+    pub(crate) fn files(&self) -> impl Iterator<Item = (&Url, &[IndexedSymbol])> {
+        self.files
+            .iter()
+            .map(|(uri, symbols)| (uri, symbols.as_slice()))
+    }
+}
+
+/// Whether the parsed file is a CloudFormation template rather than any other YAML or JSON
+///
+/// Templates either declare their format version, or have resources with an AWS type.
+pub(crate) fn is_template(template: &Template) -> bool {
+    template.format_version.is_some()
+        || template
+            .resources
+            .iter()
+            .flat_map(|section| &section.entries)
+            .filter_map(|resource| resource.resource_type.as_ref())
+            .any(|resource_type| resource_type.value.starts_with("AWS::"))
+}
+
+/// The logical IDs, parameters and export names declared by a template
+///
+/// `range` converts the spans of the template into LSP ranges of its text.
+pub(crate) fn template_symbols(
+    template: &Template,
+    range: impl Fn(Span) -> Range,
+) -> Vec<IndexedSymbol> {
+    let mut symbols = Vec::new();
+    for resource in template.resources.iter().flat_map(|s| &s.entries) {
+        symbols.push(IndexedSymbol {
+            name: resource.name.value.clone(),
+            kind: SymbolKind::STRUCT,
+            container: "Resources",
+            range: range(resource.name.span),
+        });
+    }
+    for parameter in template.parameters.iter().flat_map(|s| &s.entries) {
+        symbols.push(IndexedSymbol {
+            name: parameter.name.value.clone(),
+            kind: SymbolKind::VARIABLE,
+            container: "Parameters",
+            range: range(parameter.name.span),
+        });
+    }
+    for export in template
+        .outputs
+        .iter()
+        .flat_map(|s| &s.entries)
+        .filter_map(|output| output.export_name.as_ref())
+    {
+        // export names are usually prefixed with the stack name by a `Sub`, which is kept as
+        // written so it can still be searched for
+        let name = match &export.value {
+            Value::String(name) => name,
+            value => match value.as_intrinsic() {
+                Some((function, argument)) if function.value == "Fn::Sub" => {
+                    match &argument.value {
+                        Value::String(name) => name,
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            },
+        };
+        symbols.push(IndexedSymbol {
+            name: name.clone(),
+            kind: SymbolKind::CONSTANT,
+            container: "Exports",
+            range: range(export.span),
+        });
+    }
+    symbols
+}
+
+/// Parse a file and collect its symbols, if it is a template
+pub(crate) fn index_file(path: &Path) -> Option<(Url, Option<Vec<IndexedSymbol>>)> {
+    let uri = Url::from_file_path(path).ok()?;
+    let Ok(text) = std::fs::read_to_string(path) else {
+        // the file has gone or is not text, so it holds no symbols
+        return Some((uri, None));
+    };
+    let language = detect_template_language(path, &text);
+    let template = match reparse_tree(&text, language, None) {
+        Ok(tree) => Template::from_tree(&tree, &text, language),
+        Err(e) => {
+            tracing::debug!(error = %e, path = %path.display(), "could not parse file");
+            return Some((uri, None));
+        }
+    };
+    let rope = Rope::from_str(&text);
+    let symbols =
+        is_template(&template).then(|| template_symbols(&template, |span| lsp_range(&rope, span)));
+    Some((uri, symbols))
+}
+
+/// Whether the file has an extension that templates are written with
+pub(crate) fn is_template_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| TEMPLATE_EXTENSIONS.contains(&extension))
+}
+
+/// Every file under the root that may hold a template, skipping hidden and dependency
+/// directories
+pub(crate) fn template_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::debug!(error = %e, directory = %directory.display(), "could not read directory");
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRECTORIES.contains(&name.as_ref()) {
+                    directories.push(path);
+                }
+            } else if file_type.is_file() && is_template_path(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Index every template under the roots
+pub(crate) fn index_roots(roots: &[PathBuf]) -> WorkspaceIndex {
+    let mut index = WorkspaceIndex::default();
+    for root in roots {
+        for path in template_files(root) {
+            if let Some((uri, Some(symbols))) = index_file(&path) {
+                index.files.insert(uri, symbols);
+            }
+        }
+    }
+    tracing::debug!(files = index.files.len(), "indexed workspace templates");
+    index
+}
+
+/// Symbols whose name matches the query, as the LSP symbol information
+pub(crate) fn search<'s>(
+    query: &str,
+    files: impl IntoIterator<Item = (&'s Url, &'s [IndexedSymbol])>,
+) -> Vec<SymbolInformation> {
+    let mut results = Vec::new();
+    for (uri, symbols) in files {
+        for symbol in symbols {
+            if !fuzzy_match(query, &symbol.name) {
+                continue;
+            }
+            // `deprecated` is superseded by `tags`, but has no default
+            #[allow(deprecated)]
+            results.push(SymbolInformation {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                tags: None,
+                deprecated: None,
+                location: Location {
+                    uri: uri.clone(),
+                    range: symbol.range,
+                },
+                container_name: Some(symbol.container.to_string()),
+            });
+        }
+    }
+    results.sort_by(|a, b| {
+        (&a.name, a.location.uri.as_str()).cmp(&(&b.name, b.location.uri.as_str()))
+    });
+    results
+}
+
+/// Whether the characters of the query appear in order in the name, ignoring case
+fn fuzzy_match(query: &str, name: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| name.any(|c| c == q))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TemplateLanguage;
+
+    fn root() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/workspace")
+    }
+
+    #[test]
+    fn template_detection() {
+        for (contents, language, expected) in [
+            (
+                "AWSTemplateFormatVersion: 2010-09-09\n",
+                TemplateLanguage::Yaml,
+                true,
+            ),
+            (
+                "Resources:\n  Bucket:\n    Type: AWS::S3::Bucket\n",
+                TemplateLanguage::Yaml,
+                true,
+            ),
+            (
+                "Resources:\n  Thing:\n    Type: Other\n",
+                TemplateLanguage::Yaml,
+                false,
+            ),
+            ("name: build\non: push\n", TemplateLanguage::Yaml, false),
+            (r#"{"name": "cfn-lsp"}"#, TemplateLanguage::Json, false),
+        ] {
+            let template = Template::parse(contents, language).unwrap();
+            assert_eq!(is_template(&template), expected, "{contents}");
+        }
+    }
+
+    #[test]
+    fn finding_template_files() {
+        let root = root();
+        let files: Vec<_> = template_files(&root)
+            .iter()
+            .map(|path| path.strip_prefix(&root).unwrap().display().to_string())
+            .collect();
+        assert_eq!(
+            files,
+            ["app/service.template", "network.yml", "package.json"]
+        );
+    }
+
+    #[test]
+    fn indexing_a_workspace() {
+        let index = index_roots(&[root()]);
+        let results: Vec<_> = search("", index.files())
+            .into_iter()
+            .map(|symbol| {
+                let file = symbol
+                    .location
+                    .uri
+                    .path()
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+                    .to_string();
+                (symbol.name, symbol.container_name.unwrap(), file)
+            })
+            .collect();
+        insta::assert_yaml_snapshot!(results);
+    }
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///workspace/{name}")).unwrap()
+    }
+
+    fn symbol(name: &str) -> IndexedSymbol {
+        IndexedSymbol {
+            name: name.to_string(),
+            kind: SymbolKind::STRUCT,
+            container: "Resources",
+            range: Range::default(),
+        }
+    }
+
+    #[test]
+    fn watcher_updates_win_over_a_walk() {
+        let mut index = WorkspaceIndex::default();
+        // changed and deleted while the workspace was being walked
+        let sequence = index.next_sequence();
+        index.update(uri("changed.yml"), Some(vec![symbol("New")]), sequence);
+        index.update(uri("deleted.yml"), None, sequence);
+
+        let mut walked = WorkspaceIndex::default();
+        for name in ["changed.yml", "deleted.yml", "other.yml"] {
+            walked.files.insert(uri(name), vec![symbol("Old")]);
+        }
+        index.merge(walked);
+
+        let mut files: Vec<_> = index
+            .files()
+            .map(|(uri, symbols)| (uri.path().to_string(), symbols[0].name.clone()))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                ("/workspace/changed.yml".to_string(), "New".to_string()),
+                ("/workspace/other.yml".to_string(), "Old".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn outdated_watcher_updates_are_skipped() {
+        let mut index = WorkspaceIndex::default();
+        let first = index.next_sequence();
+        let second = index.next_sequence();
+        // the second change was read first
+        index.update(uri("template.yml"), Some(vec![symbol("New")]), second);
+        index.update(uri("template.yml"), Some(vec![symbol("Old")]), first);
+        index.update(uri("other.yml"), Some(vec![symbol("Other")]), first);
+
+        let mut files: Vec<_> = index
+            .files()
+            .map(|(uri, symbols)| (uri.path().to_string(), symbols[0].name.clone()))
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                ("/workspace/other.yml".to_string(), "Other".to_string()),
+                ("/workspace/template.yml".to_string(), "New".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_match("", "Vpc"));
+        assert!(fuzzy_match("vpc", "Vpc"));
+        assert!(fuzzy_match("pubsub", "PublicSubnet"));
+        assert!(fuzzy_match(
+            "${aws::stackname}-vpc",
+            "${AWS::StackName}-VpcId"
+        ));
+        assert!(!fuzzy_match("subpub", "PublicSubnet"));
+        assert!(!fuzzy_match("vpcs", "Vpc"));
+    }
+}